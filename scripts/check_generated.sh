@@ -0,0 +1,21 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+# Usage: ./scripts/check_generated.sh
+#
+# Fails if src/types/generated.rs differs from the result of running
+# scripts/postprocess_generated.sh on it, e.g. when a field was added to
+# config/secret_fields.txt without regenerating the endpoints.
+
+REPO_ROOT="$(cd "$(dirname "$0")/.." && pwd)"
+GENERATED="$REPO_ROOT/src/types/generated.rs"
+EXPECTED="$(mktemp)"
+trap 'rm -f "$EXPECTED"' EXIT
+
+cp "$GENERATED" "$EXPECTED"
+"$REPO_ROOT/scripts/postprocess_generated.sh" "$EXPECTED"
+
+if ! diff -u "$GENERATED" "$EXPECTED"; then
+    echo "src/types/generated.rs is out of date, run ./scripts/regenerate_endpoints.sh" >&2
+    exit 1
+fi
@@ -0,0 +1,23 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+# Usage: ./scripts/postprocess_generated.sh [generated.rs]
+#
+# Types the secret fields listed in config/secret_fields.txt in the output of
+# endpoint-gen (src/types/generated.rs by default). Idempotent: running it on
+# an already post-processed file leaves it unchanged.
+
+REPO_ROOT="$(cd "$(dirname "$0")/.." && pwd)"
+GENERATED="${1:-$REPO_ROOT/src/types/generated.rs}"
+SECRET_FIELDS="$REPO_ROOT/config/secret_fields.txt"
+IMPORT='use crate::types::credentials::{AccessToken, RedactedString};'
+
+if ! grep -qxF "$IMPORT" "$GENERATED"; then
+    IMPORT="$IMPORT" perl -0pi -e 's/\A/$ENV{IMPORT}\n/' "$GENERATED"
+fi
+
+grep -Ev '^[[:space:]]*(#|$)' "$SECRET_FIELDS" | while read -r path type; do
+    struct=${path%%.*}
+    field=${path#*.}
+    perl -0pi -e "s/(pub struct ${struct} \{[^}]*?pub ${field}: (?:Option<)?)(?:String|AccessToken|RedactedString)\b/\${1}${type}/ or die \"${struct}.${field} not found\n\"" "$GENERATED"
+done
@@ -1,2 +1,5 @@
 endpoint-gen --config-dir config/
 cp generated/model.rs src/types/generated.rs
+
+# Type the secret fields listed in config/secret_fields.txt
+./scripts/postprocess_generated.sh src/types/generated.rs
@@ -1,25 +1,105 @@
+use endpoint_libs::libs::error_code::ErrorCode;
 use psc_nanoid::{Nanoid, alphabet::Base62Alphabet};
-use secrecy::ExposeSecret;
-use url::Url;
+use secrecy::SecretString;
+use url::{Url, form_urlencoded};
 
 use crate::{
     HoneyIdConfig,
+    client::auth_session::PendingPasswordSession,
     client::honeyid_ws_conn::HoneyIdConnection,
+    client::role_connection::{HoneyIdRoleConnection, roles},
+    credentials::AccessToken,
     endpoints::auth_flow::{
-        HoneySubmitPasswordRequest, HoneySubmitPasswordResponse, HoneySubmitUsernameRequest,
-        HoneySubmitUsernameResponse,
+        HoneySignupError, HoneySignupRequest, HoneySubmitPasswordError, HoneySubmitUsernameError,
+        HoneySubmitUsernameRequest,
+    },
+    endpoints::connect::{
+        HoneyApiKeyConnectError, HoneyApiKeyConnectRequest, HoneyAuthorizedConnectError, HoneyAuthorizedConnectRequest,
     },
     enums::HoneyEndpointMethodCode,
-    types::error::HoneyIdResult,
+    types::error::{HoneyIdEndpointError, HoneyIdEndpointResult, HoneyIdError, HoneyIdResult},
 };
 
+pub mod auth_session;
 pub mod honeyid_ws_conn;
+pub mod platform_client;
+pub mod reconnecting;
+pub mod role_connection;
 
 #[derive(Debug)]
 pub struct HoneyIdClient {
     config: HoneyIdConfig,
 }
 
+/// Successful result of [`HoneyIdClient::signup`].
+#[derive(Debug, Clone)]
+pub struct SignupOutcome {
+    /// Access token of the registered `User`.
+    pub access_token: AccessToken,
+    /// Encryption key of the registered `User`.
+    pub encryption_key: SecretString,
+}
+
+/// Successful result of [`HoneyIdClient::sign_in`].
+#[derive(Debug, Clone)]
+pub struct SignInOutcome {
+    /// Access token of the signed in `User`.
+    pub access_token: AccessToken,
+    /// Encryption key of the signed in `User`.
+    pub encryption_key: SecretString,
+    /// Unix timestamp (milliseconds) at which the username step of the auth
+    /// flow expires, as returned by [`SubmitUsername`].
+    ///
+    /// [`SubmitUsername`]: HoneySubmitUsernameRequest
+    pub username_expires_at: i64,
+}
+
+/// Error returned by [`HoneyIdClient::sign_in`].
+#[derive(Debug, Clone, derive_more::Display)]
+pub enum SignInError {
+    /// The username step expired before the password could be submitted.
+    #[display("Username step of the auth flow expired at {expires_at}")]
+    UsernameStepExpired { expires_at: i64 },
+    /// The Auth server rejected the username.
+    #[display("{_0:?}")]
+    SubmitUsername(HoneySubmitUsernameError),
+    /// The Auth server rejected the password.
+    #[display("{_0:?}")]
+    SubmitPassword(HoneySubmitPasswordError),
+    #[display("{_0}")]
+    Other(HoneyIdError),
+}
+
+impl From<HoneyIdError> for SignInError {
+    fn from(value: HoneyIdError) -> Self {
+        Self::Other(value)
+    }
+}
+
+impl From<HoneyIdEndpointError<HoneySubmitUsernameError>> for SignInError {
+    fn from(value: HoneyIdEndpointError<HoneySubmitUsernameError>) -> Self {
+        match value {
+            HoneyIdEndpointError::Endpoint(err) => Self::SubmitUsername(err),
+            HoneyIdEndpointError::Other(err) => Self::Other(err),
+        }
+    }
+}
+
+impl From<HoneyIdEndpointError<HoneySubmitPasswordError>> for SignInError {
+    fn from(value: HoneyIdEndpointError<HoneySubmitPasswordError>) -> Self {
+        match value {
+            HoneyIdEndpointError::Endpoint(err) => Self::SubmitPassword(err),
+            HoneyIdEndpointError::Other(err) => Self::Other(err),
+        }
+    }
+}
+
+impl From<eyre::Report> for SignInError {
+    fn from(value: eyre::Report) -> Self {
+        Self::Other(value.into())
+    }
+}
+
 #[derive(Debug, Clone, derive_more::Display)]
 pub enum ApiKeyError {
     #[display(
@@ -28,6 +108,27 @@ pub enum ApiKeyError {
     IncorrectKey,
 }
 
+/// Slot of the configured `Auth` API key matched by
+/// [`HoneyIdClient::validate_auth_api_key`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, derive_more::Display)]
+pub enum ApiKeySlot {
+    /// [`HoneyIdConfig::auth_api_key`].
+    #[display("primary")]
+    Primary,
+    /// Entry of [`HoneyIdConfig::secondary_auth_api_keys`] at the given index.
+    #[display("secondary[{_0}]")]
+    Secondary(usize),
+}
+
+impl ApiKeySlot {
+    fn from_index(index: usize) -> Self {
+        match index {
+            0 => Self::Primary,
+            index => Self::Secondary(index - 1),
+        }
+    }
+}
+
 impl HoneyIdClient {
     /// Created new [`HoneyIdClient`] with provided [`HoneyIdConfig`].
     #[must_use]
@@ -35,85 +136,243 @@ impl HoneyIdClient {
         Self { config }
     }
 
+    /// Returns the [`HoneyIdConfig`] of this client.
+    pub fn config(&self) -> &HoneyIdConfig {
+        &self.config
+    }
+
     pub fn get_app_pub_id(&self) -> Nanoid<16, Base62Alphabet> {
         self.config.app_public_id
     }
 
-    pub fn validate_auth_api_key(&self, key: &str) -> Result<(), ApiKeyError> {
-        if self.config.auth_api_key.expose_secret() == key {
-            Ok(())
-        } else {
-            Err(ApiKeyError::IncorrectKey)
+    /// Checks `key` against the [`configured`] `Auth` API keys in constant
+    /// time, returning the [`ApiKeySlot`] of the matching one.
+    ///
+    /// [`configured`]: HoneyIdConfig::auth_api_key
+    pub fn validate_auth_api_key(&self, key: &str) -> Result<ApiKeySlot, ApiKeyError> {
+        let configured_keys = std::iter::once(&self.config.auth_api_key)
+            .chain(&self.config.secondary_auth_api_keys)
+            .enumerate();
+
+        // Every configured key is compared, so the timing doesn't tell which
+        // slot matched either.
+        let mut matched = None;
+        for (index, configured_key) in configured_keys {
+            if configured_key.ct_eq(key) && matched.is_none() {
+                matched = Some(ApiKeySlot::from_index(index));
+            }
         }
+
+        let slot = matched.ok_or(ApiKeyError::IncorrectKey)?;
+        tracing::debug!(%slot, "Auth API key matched");
+
+        Ok(slot)
+    }
+
+    /// Calls [`Signup`] endpoint of `honey.id` with [`configured`]
+    /// `appPublicId` on a new [`public`] connection, registering a new `User`
+    /// with provided `username` and `password`.
+    ///
+    /// Returns the `User`'s access token and encryption key as a [`SignupOutcome`].
+    ///
+    /// [`Signup`]: HoneySignupRequest
+    /// [`configured`]: crate::HoneyIdConfig
+    /// [`public`]: crate::enums::HoneyUserRole::Public
+    pub async fn signup(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> HoneyIdEndpointResult<SignupOutcome, HoneySignupError> {
+        let conn = self.connect_public().await?;
+
+        let response = conn
+            .request_typed(HoneySignupRequest {
+                appPublicId: self.config.app_public_id,
+                username: username.to_string(),
+                password: password.into(),
+            })
+            .await?;
+
+        Ok(SignupOutcome {
+            access_token: response.accessToken,
+            encryption_key: response.encryptionKey.into(),
+        })
     }
 
-    // TODOVEON: signup call for use in API
+    /// Runs the whole `honey.id` authentication flow, [`SubmitUsername`]
+    /// followed by [`SubmitPassword`], on a new [`public`] connection.
+    ///
+    /// Fails with [`SignInError::UsernameStepExpired`] without sending the
+    /// password if the username step has already expired.
+    ///
+    /// [`SubmitUsername`]: HoneySubmitUsernameRequest
+    /// [`SubmitPassword`]: crate::endpoints::auth_flow::HoneySubmitPasswordRequest
+    /// [`public`]: crate::enums::HoneyUserRole::Public
+    pub async fn sign_in(&self, username: &str, password: &str) -> Result<SignInOutcome, SignInError> {
+        let session = self.submit_username(username).await?;
 
-    pub async fn sign_in(&self, username: &str, password: &str) -> HoneyIdResult<String> {
-        let (_username_response, mut session_conn) = self.submit_username(username).await?;
+        if session.is_expired() {
+            return Err(SignInError::UsernameStepExpired {
+                expires_at: session.expires_at(),
+            });
+        }
 
-        // TODO: Check expires at timestamp, not really necessary though
-        let submit_password_return = self.submit_password(&mut session_conn, password).await?;
+        let session = session.submit_password(password).await?;
 
-        HoneyIdResult::Ok(submit_password_return.accessToken)
+        Ok(SignInOutcome {
+            access_token: session.access_token(),
+            encryption_key: session.encryption_key().clone(),
+            username_expires_at: session.username_expires_at(),
+        })
     }
 
     /// Calls [`SubmitUsername`] endpoint of `honey.id` with [`configured`]
-    /// `appPublicId` using provided [`HoneyIdConnection`] and `username`.
+    /// `appPublicId` and provided `username` on a new [`public`] connection.
     ///
-    /// [`SubmitUsername`] should be used to start `honey.id` authentication
-    /// process using [`public`] connection.
+    /// [`SubmitUsername`] starts the `honey.id` authentication flow, which is
+    /// completed with [`PendingPasswordSession::submit_password`].
     ///
-    /// [`SubmitUsername`]: SubmitUsernameRequest
+    /// [`SubmitUsername`]: HoneySubmitUsernameRequest
     /// [`configured`]: crate::HoneyIdConfig
-    /// [`public`]: crate::enums::UserRole::Public
+    /// [`public`]: crate::enums::HoneyUserRole::Public
     pub async fn submit_username(
         &self,
         username: &str,
-    ) -> HoneyIdResult<(HoneySubmitUsernameResponse, HoneyIdConnection)> {
-        let mut conn = self.connect_public().await?;
+    ) -> HoneyIdEndpointResult<PendingPasswordSession, HoneySubmitUsernameError> {
+        let conn = self.connect_public().await?;
 
-        conn.send_request(
-            HoneyEndpointMethodCode::SubmitUsername,
-            HoneySubmitUsernameRequest {
+        let response = conn
+            .request_typed(HoneySubmitUsernameRequest {
                 appPublicId: self.config.app_public_id,
                 username: username.to_string(),
-            },
-        )
-        .await?;
+            })
+            .await?;
 
-        let response = conn.receive_response::<HoneySubmitUsernameResponse>().await?;
+        Ok(PendingPasswordSession::new(conn, response.expiresAt))
+    }
+
+    pub async fn raw_connect(addr: &Url, header: &str) -> HoneyIdResult<HoneyIdConnection> {
+        HoneyIdConnection::connect(addr, Some(header)).await
+    }
+
+    /// Opens a new connection to the [`configured`] address with `header`,
+    /// bounding the connect and every call by the configured request timeout.
+    ///
+    /// [`configured`]: HoneyIdConfig::addr
+    async fn open(&self, header: &str) -> HoneyIdResult<HoneyIdConnection> {
+        HoneyIdConnection::connect_with_timeout(&self.config.addr, Some(header), self.config.request_timeout()).await
+    }
+
+    /// Opens a new [`public`] connection, with the [`configured`] request
+    /// timeout.
+    ///
+    /// [`public`]: crate::enums::HoneyUserRole::Public
+    /// [`configured`]: HoneyIdConfig::request_timeout_ms
+    pub async fn connect_public(&self) -> HoneyIdResult<HoneyIdRoleConnection<roles::Public>> {
+        let header = Self::connect_header(HoneyEndpointMethodCode::PublicConnect, &[])?;
+        let conn = self.open(&header).await?;
 
-        HoneyIdResult::Ok((response, conn))
+        Ok(HoneyIdRoleConnection::new(conn))
     }
 
-    pub async fn submit_password(
+    /// Opens a new connection authorized via [`AuthorizedConnect`] with
+    /// `access_token`, acting as the signed in `User` it was issued to.
+    ///
+    /// [`AuthorizedConnect`]: HoneyAuthorizedConnectRequest
+    pub async fn connect_authorized(
         &self,
-        session_conn: &mut HoneyIdConnection,
-        password: &str,
-    ) -> HoneyIdResult<HoneySubmitPasswordResponse> {
-        session_conn
-            .send_request(
-                HoneyEndpointMethodCode::SubmitPassword,
-                HoneySubmitPasswordRequest {
-                    password: password.to_string(),
-                },
-            )
-            .await?;
+        access_token: AccessToken,
+    ) -> HoneyIdEndpointResult<HoneyIdRoleConnection<roles::Authorized>, HoneyAuthorizedConnectError> {
+        let header = Self::connect_header(
+            HoneyEndpointMethodCode::AuthorizedConnect,
+            &[&access_token.expose_secret().to_string()],
+        )?;
+        let conn = self.open(&header).await?;
+        conn.receive_handshake::<HoneyAuthorizedConnectRequest>().await?;
+
+        Ok(HoneyIdRoleConnection::new(conn))
+    }
 
-        let response = session_conn.receive_response::<HoneySubmitPasswordResponse>().await?;
+    /// Opens a new connection authorized via [`ApiKeyConnect`] with the
+    /// [`configured`] `Auth` API key.
+    ///
+    /// [`ApiKeyConnect`]: HoneyApiKeyConnectRequest
+    /// [`configured`]: HoneyIdConfig::auth_api_key
+    pub async fn connect_api_key(
+        &self,
+    ) -> HoneyIdEndpointResult<HoneyIdRoleConnection<roles::ApiKey>, HoneyApiKeyConnectError> {
+        let header = Self::connect_header(
+            HoneyEndpointMethodCode::ApiKeyConnect,
+            &[self.config.auth_api_key.expose_secret()],
+        )?;
+        let conn = self.open(&header).await?;
+        conn.receive_handshake::<HoneyApiKeyConnectRequest>().await?;
 
-        HoneyIdResult::Ok(response)
+        Ok(HoneyIdRoleConnection::new(conn))
     }
 
-    pub async fn raw_connect(addr: &Url, header: &str) -> HoneyIdResult<HoneyIdConnection> {
-        HoneyIdConnection::connect(addr, Some(header)).await
+    /// Builds the WebSocket protocol header for the connect endpoint `method`.
+    ///
+    /// The header has the form `0<endpoint name>,1<param 1>,2<param 2>...`,
+    /// with `params` given in the order of the endpoint's schema parameters.
+    ///
+    /// Fails with [`ErrorCode::BAD_REQUEST`] if the number of `params`
+    /// doesn't match the endpoint's schema.
+    pub fn connect_header(method: HoneyEndpointMethodCode, params: &[&str]) -> HoneyIdResult<String> {
+        let schema = method.schema();
+        if params.len() != schema.parameters.len() {
+            return Err(HoneyIdError::new(
+                ErrorCode::BAD_REQUEST,
+                format!(
+                    "{} connect header takes {} parameters, got {}",
+                    schema.name,
+                    schema.parameters.len(),
+                    params.len()
+                ),
+            ));
+        }
+
+        let auth_endpoint_name = schema.name.to_lowercase();
+        let mut header = format!("0{auth_endpoint_name}");
+        for (index, param) in params.iter().enumerate() {
+            let param: String = form_urlencoded::byte_serialize(param.as_bytes()).collect();
+            header.push_str(&format!(",{}{param}", index + 1));
+        }
+
+        Ok(header)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::credentials::AppApiKey;
+
+    #[test]
+    fn validates_primary_and_secondary_auth_api_keys() {
+        let client = HoneyIdClient::new(HoneyIdConfig {
+            auth_api_key: AppApiKey::from("new-key"),
+            secondary_auth_api_keys: vec![AppApiKey::from("old-key"), AppApiKey::from("older-key")],
+            ..HoneyIdConfig::default()
+        });
+
+        assert_eq!(client.validate_auth_api_key("new-key").unwrap(), ApiKeySlot::Primary);
+        assert_eq!(
+            client.validate_auth_api_key("older-key").unwrap(),
+            ApiKeySlot::Secondary(1)
+        );
+        assert!(client.validate_auth_api_key("new-ke").is_err());
+        assert!(client.validate_auth_api_key("").is_err());
     }
 
-    pub async fn connect_public(&self) -> HoneyIdResult<HoneyIdConnection> {
-        let auth_endpoint_name = HoneyEndpointMethodCode::PublicConnect.schema().name.to_lowercase();
-        let header = format!("0{auth_endpoint_name}");
+    #[test]
+    fn builds_connect_header_with_schema_parameters() {
+        assert_eq!(
+            HoneyIdClient::connect_header(HoneyEndpointMethodCode::ApiKeyConnect, &["key/+"]).unwrap(),
+            "0apikeyconnect,1key%2F%2B"
+        );
 
-        Self::raw_connect(&self.config.addr, &header).await
+        let err = HoneyIdClient::connect_header(HoneyEndpointMethodCode::ApiKeyConnect, &[]).unwrap_err();
+        assert_eq!(err.code, ErrorCode::BAD_REQUEST);
     }
 }
@@ -0,0 +1,135 @@
+//! Definition of [`PendingPasswordSession`] and [`AuthenticatedSession`].
+//!
+//! Typestate of the `honey.id` authentication flow: a password can only be
+//! submitted on the connection that submitted the username, so the flow can't
+//! be run out of order.
+
+use endpoint_libs::libs::utils::get_time_milliseconds;
+use secrecy::SecretString;
+
+use crate::client::role_connection::{HoneyIdRoleConnection, roles};
+use crate::credentials::AccessToken;
+use crate::endpoints::auth_flow::{HoneySubmitPasswordError, HoneySubmitPasswordRequest};
+use crate::types::error::HoneyIdEndpointResult;
+
+/// Auth flow whose username was accepted, returned by
+/// [`HoneyIdClient::submit_username`].
+///
+/// [`HoneyIdClient::submit_username`]: crate::HoneyIdClient::submit_username
+#[derive(Debug)]
+pub struct PendingPasswordSession {
+    conn: HoneyIdRoleConnection<roles::Public>,
+    expires_at: i64,
+}
+
+impl PendingPasswordSession {
+    pub(crate) fn new(conn: HoneyIdRoleConnection<roles::Public>, expires_at: i64) -> Self {
+        Self { conn, expires_at }
+    }
+
+    /// Unix timestamp (milliseconds) at which the username step expires, as
+    /// returned by [`SubmitUsername`].
+    ///
+    /// [`SubmitUsername`]: crate::endpoints::auth_flow::HoneySubmitUsernameRequest
+    pub fn expires_at(&self) -> i64 {
+        self.expires_at
+    }
+
+    /// Returns `true` if the username step has expired, so submitting the
+    /// password would be refused.
+    pub fn is_expired(&self) -> bool {
+        get_time_milliseconds() >= self.expires_at
+    }
+
+    /// Calls [`SubmitPassword`] endpoint of `honey.id` on the connection of
+    /// this session, turning it into an [`AuthenticatedSession`].
+    ///
+    /// [`SubmitPassword`]: HoneySubmitPasswordRequest
+    pub async fn submit_password(
+        self,
+        password: &str,
+    ) -> HoneyIdEndpointResult<AuthenticatedSession, HoneySubmitPasswordError> {
+        let response = self
+            .conn
+            .request_typed(HoneySubmitPasswordRequest {
+                password: password.into(),
+            })
+            .await?;
+
+        Ok(AuthenticatedSession {
+            conn: self.conn,
+            access_token: response.accessToken,
+            encryption_key: response.encryptionKey.into(),
+            username_expires_at: self.expires_at,
+        })
+    }
+}
+
+/// Auth flow completed by [`PendingPasswordSession::submit_password`].
+#[derive(Debug)]
+pub struct AuthenticatedSession {
+    conn: HoneyIdRoleConnection<roles::Public>,
+    access_token: AccessToken,
+    encryption_key: SecretString,
+    username_expires_at: i64,
+}
+
+impl AuthenticatedSession {
+    /// Access token of the signed in `User`.
+    pub fn access_token(&self) -> AccessToken {
+        self.access_token
+    }
+
+    /// Encryption key of the signed in `User`.
+    pub fn encryption_key(&self) -> &SecretString {
+        &self.encryption_key
+    }
+
+    /// Unix timestamp (milliseconds) at which the username step of the flow
+    /// expired.
+    pub fn username_expires_at(&self) -> i64 {
+        self.username_expires_at
+    }
+
+    /// Returns the public connection the flow ran on.
+    pub fn connection(&self) -> &HoneyIdRoleConnection<roles::Public> {
+        &self.conn
+    }
+
+    /// Consumes this session, returning the public connection the flow ran
+    /// on.
+    pub fn into_connection(self) -> HoneyIdRoleConnection<roles::Public> {
+        self.conn
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HoneyIdClient;
+    use crate::testing::MockHoneyIdServer;
+
+    #[tokio::test]
+    async fn submits_password_on_username_connection() {
+        let server = MockHoneyIdServer::start(SecretString::from("platform-key"))
+            .await
+            .unwrap();
+        let app = server.create_app(None);
+        let client = HoneyIdClient::new(app.config(&server));
+        client.signup("alice", "correct horse").await.unwrap();
+
+        let session = client.submit_username("alice").await.unwrap();
+        assert!(!session.is_expired());
+        let expires_at = session.expires_at();
+
+        let session = session.submit_password("correct horse").await.unwrap();
+        assert_eq!(session.username_expires_at(), expires_at);
+
+        let session = client.submit_username("alice").await.unwrap();
+        let err = session.submit_password("wrong horse").await.unwrap_err();
+        assert!(matches!(
+            err.endpoint_error(),
+            Some(HoneySubmitPasswordError::InvalidPassword)
+        ));
+    }
+}
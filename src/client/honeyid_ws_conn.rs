@@ -1,13 +1,75 @@
-use endpoint_libs::libs::ws::{WsClient, WsClientBuilder, WsResponseGeneric, WsVersionMode};
+use endpoint_libs::libs::error_code::ErrorCode;
+use endpoint_libs::libs::ws::{WsRequestGeneric, WsResponseError, WsResponseGeneric};
 use eyre::bail;
-use serde::{Deserialize, Serialize};
+use futures::{SinkExt, StreamExt};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Debug;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::http::header::SEC_WEBSOCKET_PROTOCOL;
+use tokio_tungstenite::tungstenite::{self, Message};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
 use url::Url;
 
 use crate::enums::HoneyEndpointMethodCode;
-use crate::types::error::{HoneyIdError, HoneyIdResult};
+use crate::types::error::{
+    HoneyEndpointRequest, HoneyIdEndpointError, HoneyIdEndpointResult, HoneyIdError, HoneyIdResult,
+};
 
+/// Frame sent by the Auth server, with `params` left undecoded.
+pub type HoneyIdServerFrame = WsResponseGeneric<Value>;
+
+/// Number of unread server-pushed frames kept for each
+/// [`HoneyIdConnection::server_messages`] subscriber.
+const SERVER_MESSAGES_CAPACITY: usize = 64;
+
+/// Timeout of connecting and of calls made on a [`HoneyIdConnection`], unless
+/// overridden with [`HoneyIdConnection::connect_with_timeout`],
+/// [`HoneyIdConnection::with_request_timeout`] or per call.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Number of cancelled calls remembered by a [`HoneyIdConnection`], so that
+/// their late responses are discarded.
+const MAX_CANCELLED_CALLS: usize = 1024;
+
+/// Sequence number the Auth server uses to answer the connect handshake.
+const HANDSHAKE_SEQ: u32 = 0;
+
+type Reply = oneshot::Sender<HoneyIdResult<HoneyIdServerFrame>>;
+
+type ReplyReceiver = oneshot::Receiver<HoneyIdResult<HoneyIdServerFrame>>;
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// WebSocket connection to `honey.id`.
+///
+/// Every request is tagged with its own sequence number, and a background
+/// reader hands each response to the call waiting for it, so any number of
+/// calls can be in flight at once. Share the connection between tasks by
+/// wrapping it in an [`Arc`](std::sync::Arc).
+///
+/// Connecting and calls fail with [`HoneyIdError::timeout`] if the Auth server
+/// doesn't answer within the request timeout. Dropping a call future cancels the
+/// call; its response is discarded if it arrives later.
+///
+/// The socket is closed once the connection is dropped.
 pub struct HoneyIdConnection {
-    client: WsClient,
+    commands: mpsc::UnboundedSender<Command>,
+    next_seq: AtomicU32,
+    request_timeout: Duration,
+    handshake: Mutex<Option<ReplyReceiver>>,
+    server_messages: broadcast::Sender<HoneyIdServerFrame>,
+    /// Calls sent with the deprecated [`send_request_raw`](Self::send_request_raw),
+    /// oldest first, whose response wasn't received yet.
+    unanswered: Mutex<VecDeque<(u32, ReplyReceiver)>>,
 }
 
 impl std::fmt::Debug for HoneyIdConnection {
@@ -17,40 +79,631 @@ impl std::fmt::Debug for HoneyIdConnection {
 }
 
 impl HoneyIdConnection {
+    /// Same as [`connect_with_timeout`] with [`DEFAULT_REQUEST_TIMEOUT`].
+    ///
+    /// [`connect_with_timeout`]: Self::connect_with_timeout
     pub async fn connect(addr: &Url, auth: Option<&str>) -> HoneyIdResult<HoneyIdConnection> {
-        let (client, _) = WsClientBuilder::new()
-            .mode(WsVersionMode::Auto)
-            .protocol_header(auth.unwrap_or(""))
-            .build(addr.as_str())
-            .await?;
-        Ok(HoneyIdConnection { client })
+        Self::connect_with_timeout(addr, auth, DEFAULT_REQUEST_TIMEOUT).await
+    }
+
+    /// Opens a new connection to `addr`, sending `auth` as the WebSocket
+    /// protocol header.
+    ///
+    /// Fails with [`HoneyIdError::timeout`] if the TCP connect and WebSocket
+    /// upgrade don't complete within `timeout`, which is also used as the
+    /// request timeout of the connection.
+    pub async fn connect_with_timeout(
+        addr: &Url,
+        auth: Option<&str>,
+        timeout: Duration,
+    ) -> HoneyIdResult<HoneyIdConnection> {
+        let mut request = addr.as_str().into_client_request().map_err(eyre::Report::from)?;
+        if let Some(auth) = auth.filter(|auth| !auth.is_empty()) {
+            let protocol = HeaderValue::from_str(auth).map_err(eyre::Report::from)?;
+            request.headers_mut().insert(SEC_WEBSOCKET_PROTOCOL, protocol);
+        }
+        let (stream, _) = tokio::time::timeout(timeout, tokio_tungstenite::connect_async(request))
+            .await
+            .map_err(|_| HoneyIdError::timeout(timeout))?
+            .map_err(eyre::Report::from)?;
+
+        let (commands, command_rx) = mpsc::unbounded_channel();
+        let (handshake_tx, handshake_rx) = oneshot::channel();
+        let (server_messages, _) = broadcast::channel(SERVER_MESSAGES_CAPACITY);
+        let driver = ConnectionDriver {
+            commands: command_rx,
+            pending: HashMap::new(),
+            cancelled: CancelledCalls::default(),
+            handshake: Some(handshake_tx),
+            server_messages: server_messages.clone(),
+        };
+        tokio::spawn(driver.run(stream));
+
+        Ok(HoneyIdConnection {
+            commands,
+            next_seq: AtomicU32::new(HANDSHAKE_SEQ + 1),
+            request_timeout: timeout,
+            handshake: Mutex::new(Some(handshake_rx)),
+            server_messages,
+            unanswered: Mutex::new(VecDeque::new()),
+        })
+    }
+
+    /// Sets the timeout of calls that don't override it.
+    #[must_use]
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Returns the timeout of calls that don't override it.
+    pub fn request_timeout(&self) -> Duration {
+        self.request_timeout
     }
 
     /// Used specifically for [HoneyEndpointMethodCode] endpoints that are defined within this project
-    pub async fn send_request<T: Serialize>(&mut self, method: HoneyEndpointMethodCode, params: T) -> eyre::Result<()> {
-        self.send_request_raw(method as u32, params).await
+    pub async fn request<T, P>(&self, method: HoneyEndpointMethodCode, params: P) -> eyre::Result<T>
+    where
+        T: DeserializeOwned,
+        P: Serialize,
+    {
+        self.request_raw(method as u32, params).await
     }
 
     /// Used for compatibility with code that doesn't call HoneyEndpointMethodCode endpoints
-    pub async fn send_request_raw<T: Serialize>(&mut self, method: u32, params: T) -> eyre::Result<()> {
-        self.client.send_req(method, params).await
+    pub async fn request_raw<T, P>(&self, method: u32, params: P) -> eyre::Result<T>
+    where
+        T: DeserializeOwned,
+        P: Serialize,
+    {
+        self.request_raw_with_timeout(method, params, self.request_timeout)
+            .await
     }
 
-    pub async fn receive_response<T>(&mut self) -> eyre::Result<T>
+    /// Same as [`request_raw`], but fails after `timeout` instead of the
+    /// connection's request timeout.
+    ///
+    /// [`request_raw`]: Self::request_raw
+    pub async fn request_raw_with_timeout<T, P>(&self, method: u32, params: P, timeout: Duration) -> eyre::Result<T>
     where
-        T: for<'de> Deserialize<'de>,
+        T: DeserializeOwned,
+        P: Serialize,
     {
-        let raw = self.client.recv_raw().await?;
-        let resp: WsResponseGeneric<T> = serde_json::from_value(raw)?;
-        match resp {
-            WsResponseGeneric::Immediate(resp) => Ok(resp.params),
-            WsResponseGeneric::Error(err) => {
-                bail!(HoneyIdError::new(
-                    endpoint_libs::libs::error_code::ErrorCode::new(err.code),
-                    err.params.to_string()
-                ))
-            }
+        match self.call(method, params, timeout).await? {
+            WsResponseGeneric::Immediate(resp) => Ok(serde_json::from_value(resp.params)?),
+            WsResponseGeneric::Error(err) => bail!(server_error(&err)),
             _ => bail!("Unexpected response from server"),
         }
     }
+
+    /// Sends a call without waiting for its response, which is then received
+    /// with [`receive_response`](Self::receive_response).
+    #[deprecated(note = "use `request`, which waits for the response to this call")]
+    pub async fn send_request<T: Serialize>(&self, method: HoneyEndpointMethodCode, params: T) -> eyre::Result<()> {
+        #[allow(deprecated)]
+        self.send_request_raw(method as u32, params).await
+    }
+
+    /// Same as [`send_request`](Self::send_request), for methods that aren't
+    /// [HoneyEndpointMethodCode] endpoints.
+    #[deprecated(note = "use `request_raw`, which waits for the response to this call")]
+    pub async fn send_request_raw<T: Serialize>(&self, method: u32, params: T) -> eyre::Result<()> {
+        let (seq, response) = self.send_call(method, params)?;
+        lock(&self.unanswered).push_back((seq, response));
+        Ok(())
+    }
+
+    /// Receives the response to the oldest call sent with
+    /// [`send_request`](Self::send_request) that wasn't received yet.
+    ///
+    /// Responses are matched to calls by `seq`, so they are received in the
+    /// order the calls were sent, whatever order the server answers in.
+    #[deprecated(note = "use `request`, which waits for the response to this call")]
+    pub async fn receive_response<T: DeserializeOwned>(&self) -> eyre::Result<T> {
+        let unanswered = lock(&self.unanswered).pop_front();
+        let Some((seq, response)) = unanswered else {
+            bail!("No request is awaiting a response");
+        };
+        match self.await_reply(seq, response, self.request_timeout).await? {
+            WsResponseGeneric::Immediate(resp) => Ok(serde_json::from_value(resp.params)?),
+            WsResponseGeneric::Error(err) => bail!(server_error(&err)),
+            _ => bail!("Unexpected response from server"),
+        }
+    }
+
+    /// Same as [`receive_response`](Self::receive_response), but decodes
+    /// server errors into the endpoint's generated error enum, like
+    /// [`request_typed`](Self::request_typed).
+    #[deprecated(note = "use `request_typed`, which waits for the response to this call")]
+    pub async fn receive_typed<Req>(&self) -> HoneyIdEndpointResult<Req::Response, Req::Error>
+    where
+        Req: HoneyEndpointRequest,
+    {
+        let unanswered = lock(&self.unanswered).pop_front();
+        let Some((seq, response)) = unanswered else {
+            return Err(eyre::eyre!("No request is awaiting a response").into());
+        };
+        let frame = self.await_reply(seq, response, self.request_timeout).await?;
+        endpoint_response(frame)
+    }
+
+    /// Sends `req` and waits for its response, decoding server errors into
+    /// the endpoint's generated error enum (e.g. [`SubmitPasswordError`] for
+    /// [`SubmitPasswordRequest`]).
+    ///
+    /// Errors whose `kind` is not a variant of the endpoint's error enum are
+    /// returned as [`HoneyIdEndpointError::Other`].
+    ///
+    /// [`SubmitPasswordError`]: crate::endpoints::auth_flow::HoneySubmitPasswordError
+    /// [`SubmitPasswordRequest`]: crate::endpoints::auth_flow::HoneySubmitPasswordRequest
+    pub async fn request_typed<Req>(&self, req: Req) -> HoneyIdEndpointResult<Req::Response, Req::Error>
+    where
+        Req: HoneyEndpointRequest,
+    {
+        self.request_typed_with_timeout(req, self.request_timeout).await
+    }
+
+    /// Same as [`request_typed`], but fails after `timeout` instead of the
+    /// connection's request timeout.
+    ///
+    /// [`request_typed`]: Self::request_typed
+    pub async fn request_typed_with_timeout<Req>(
+        &self,
+        req: Req,
+        timeout: Duration,
+    ) -> HoneyIdEndpointResult<Req::Response, Req::Error>
+    where
+        Req: HoneyEndpointRequest,
+    {
+        let frame = self.call(Req::METHOD_ID, req, timeout).await?;
+        endpoint_response(frame)
+    }
+
+    /// Waits for the Auth server's reply to the connect handshake `Req`
+    /// (e.g. [`PlatformConnectRequest`]).
+    ///
+    /// Can only be called once per connection.
+    ///
+    /// [`PlatformConnectRequest`]: crate::endpoints::platform::PlatformConnectRequest
+    pub async fn receive_handshake<Req>(&self) -> HoneyIdEndpointResult<Req::Response, Req::Error>
+    where
+        Req: HoneyEndpointRequest,
+    {
+        let handshake = lock(&self.handshake).take();
+        let Some(handshake) = handshake else {
+            return Err(eyre::eyre!("Handshake response was already received").into());
+        };
+
+        let frame = tokio::time::timeout(self.request_timeout, handshake)
+            .await
+            .map_err(|_| HoneyIdError::timeout(self.request_timeout))?
+            .map_err(|_| connection_closed())??;
+        endpoint_response(frame)
+    }
+
+    /// Subscribes to frames that don't answer any request made on this
+    /// connection, e.g. stream data pushed by the Auth server.
+    ///
+    /// Only frames received after subscribing are delivered.
+    pub fn server_messages(&self) -> broadcast::Receiver<HoneyIdServerFrame> {
+        self.server_messages.subscribe()
+    }
+
+    /// Returns `true` once the underlying socket has been closed.
+    pub fn is_closed(&self) -> bool {
+        self.commands.is_closed()
+    }
+
+    /// Completes once the underlying socket has been closed.
+    pub async fn closed(&self) {
+        self.commands.closed().await
+    }
+
+    async fn call<P: Serialize>(&self, method: u32, params: P, timeout: Duration) -> HoneyIdResult<HoneyIdServerFrame> {
+        let (seq, response) = self.send_call(method, params)?;
+        self.await_reply(seq, response, timeout).await
+    }
+
+    fn send_call<P: Serialize>(&self, method: u32, params: P) -> HoneyIdResult<(u32, ReplyReceiver)> {
+        let seq = self.next_seq();
+        let payload = serde_json::to_string(&WsRequestGeneric { method, seq, params }).map_err(eyre::Report::from)?;
+
+        let (reply, response) = oneshot::channel();
+        self.commands
+            .send(Command::Request { seq, payload, reply })
+            .map_err(|_| connection_closed())?;
+        Ok((seq, response))
+    }
+
+    async fn await_reply(
+        &self,
+        seq: u32,
+        response: ReplyReceiver,
+        timeout: Duration,
+    ) -> HoneyIdResult<HoneyIdServerFrame> {
+        // Cancels the call if it times out or this future is dropped.
+        let guard = CancelOnDrop {
+            commands: &self.commands,
+            seq,
+        };
+        let response = tokio::time::timeout(timeout, response)
+            .await
+            .map_err(|_| HoneyIdError::timeout(timeout))?;
+        std::mem::forget(guard);
+
+        response.map_err(|_| connection_closed())?
+    }
+
+    fn next_seq(&self) -> u32 {
+        loop {
+            let seq = self.next_seq.fetch_add(1, Ordering::Relaxed);
+            if seq != HANDSHAKE_SEQ {
+                return seq;
+            }
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+struct CancelOnDrop<'a> {
+    commands: &'a mpsc::UnboundedSender<Command>,
+    seq: u32,
+}
+
+impl Drop for CancelOnDrop<'_> {
+    fn drop(&mut self) {
+        let _ = self.commands.send(Command::Cancel { seq: self.seq });
+    }
+}
+
+enum Command {
+    Request { seq: u32, payload: String, reply: Reply },
+    Cancel { seq: u32 },
+}
+
+enum Event {
+    Command(Option<Command>),
+    Message(Option<Result<Message, tungstenite::Error>>),
+}
+
+/// Calls cancelled before their response arrived, whose response is
+/// discarded instead of being published as a server message.
+///
+/// Only the last [`MAX_CANCELLED_CALLS`] are remembered, so calls the server
+/// never answers don't pile up. Late responses to older ones are published
+/// as server messages.
+#[derive(Default)]
+struct CancelledCalls {
+    seqs: HashSet<u32>,
+    /// `seqs` in cancellation order, possibly including already removed ones.
+    order: VecDeque<u32>,
+}
+
+impl CancelledCalls {
+    fn insert(&mut self, seq: u32) {
+        if self.order.len() >= MAX_CANCELLED_CALLS
+            && let Some(oldest) = self.order.pop_front()
+        {
+            self.seqs.remove(&oldest);
+        }
+        self.seqs.insert(seq);
+        self.order.push_back(seq);
+    }
+
+    fn remove(&mut self, seq: u32) -> bool {
+        if !self.seqs.remove(&seq) {
+            return false;
+        }
+        if self.order.len() > 2 * self.seqs.len() {
+            let seqs = &self.seqs;
+            self.order.retain(|seq| seqs.contains(seq));
+        }
+        true
+    }
+}
+
+/// Owns the socket and routes each received frame to the call waiting for it.
+struct ConnectionDriver {
+    commands: mpsc::UnboundedReceiver<Command>,
+    pending: HashMap<u32, Reply>,
+    cancelled: CancelledCalls,
+    handshake: Option<oneshot::Sender<HoneyIdResult<HoneyIdServerFrame>>>,
+    server_messages: broadcast::Sender<HoneyIdServerFrame>,
+}
+
+impl ConnectionDriver {
+    async fn run(mut self, mut stream: WsStream) {
+        loop {
+            let event = tokio::select! {
+                command = self.commands.recv() => Event::Command(command),
+                message = stream.next() => Event::Message(message),
+            };
+
+            match event {
+                Event::Command(Some(Command::Request { seq, payload, reply })) => {
+                    if let Err(err) = stream.send(Message::text(payload)).await {
+                        let _ = reply.send(Err(eyre::Report::from(err).into()));
+                        break;
+                    }
+                    self.pending.insert(seq, reply);
+                }
+                Event::Command(Some(Command::Cancel { seq })) => self.cancel(seq),
+                Event::Command(None) => {
+                    if let Err(err) = stream.close(None).await {
+                        tracing::debug!(?err, "Failed to close honey.id connection");
+                    }
+                    return;
+                }
+                Event::Message(Some(Ok(Message::Text(text)))) => match serde_json::from_str(&text) {
+                    Ok(frame) => {
+                        if !self.dispatch(frame) {
+                            break;
+                        }
+                    }
+                    Err(err) => tracing::debug!(?err, "Skipped non-JSON frame from honey.id"),
+                },
+                Event::Message(Some(Ok(Message::Close(_))) | None) => {
+                    tracing::debug!("honey.id connection closed");
+                    break;
+                }
+                // Pings are answered by `tungstenite` itself.
+                Event::Message(Some(Ok(message))) => {
+                    tracing::debug!(?message, "Skipped non-text frame from honey.id");
+                }
+                Event::Message(Some(Err(err))) => {
+                    tracing::debug!(?err, "honey.id connection failed");
+                    break;
+                }
+            }
+        }
+        // Dropping `self` fails every pending call with a closed connection
+        // error.
+    }
+
+    fn cancel(&mut self, seq: u32) {
+        if self.pending.remove(&seq).is_some() {
+            self.cancelled.insert(seq);
+        }
+    }
+
+    /// Routes `frame` to the call waiting for it. Returns `false` once the
+    /// server closes the connection.
+    fn dispatch(&mut self, frame: Value) -> bool {
+        let frame: HoneyIdServerFrame = match serde_json::from_value(frame) {
+            Ok(frame) => frame,
+            Err(err) => {
+                tracing::debug!(?err, "Skipped malformed frame from honey.id");
+                return true;
+            }
+        };
+
+        let seq = match &frame {
+            WsResponseGeneric::Immediate(resp) => resp.seq,
+            WsResponseGeneric::Error(err) => err.seq,
+            WsResponseGeneric::Log(log) => {
+                tracing::debug!(seq = log.seq, level = ?log.level, "honey.id: {}", log.message);
+                return true;
+            }
+            WsResponseGeneric::Forwarded(forwarded) => {
+                tracing::debug!(seq = forwarded.seq, "honey.id forwarded request");
+                return true;
+            }
+            WsResponseGeneric::Stream(_) => {
+                let _ = self.server_messages.send(frame);
+                return true;
+            }
+            WsResponseGeneric::Close => return false,
+        };
+
+        if seq == HANDSHAKE_SEQ
+            && let Some(handshake) = self.handshake.take()
+        {
+            let _ = handshake.send(Ok(frame));
+        } else if let Some(reply) = self.pending.remove(&seq) {
+            let _ = reply.send(Ok(frame));
+        } else if self.cancelled.remove(seq) {
+            tracing::debug!(seq, "Discarded response to cancelled call");
+        } else {
+            let _ = self.server_messages.send(frame);
+        }
+
+        true
+    }
+}
+
+pub(crate) fn connection_closed() -> HoneyIdError {
+    HoneyIdError::new(ErrorCode::SERVICE_UNAVAILABLE, "Connection to honey.id is closed")
+}
+
+fn endpoint_response<T, E>(frame: HoneyIdServerFrame) -> HoneyIdEndpointResult<T, E>
+where
+    T: DeserializeOwned,
+    E: DeserializeOwned + Debug,
+{
+    match frame {
+        WsResponseGeneric::Immediate(resp) => Ok(serde_json::from_value(resp.params).map_err(eyre::Report::from)?),
+        WsResponseGeneric::Error(err) => Err(decode_endpoint_error(&err)),
+        _ => Err(eyre::eyre!("Unexpected response from server").into()),
+    }
+}
+
+fn server_error(err: &WsResponseError) -> HoneyIdError {
+    HoneyIdError::new(ErrorCode::new(err.code), err.params.to_string())
+}
+
+fn decode_endpoint_error<E>(err: &WsResponseError) -> HoneyIdEndpointError<E>
+where
+    E: DeserializeOwned + Debug,
+{
+    err.params
+        .get("kind")
+        .and_then(|kind| serde_json::from_value::<E>(kind.clone()).ok())
+        .map_or_else(
+            || HoneyIdEndpointError::Other(server_error(err)),
+            HoneyIdEndpointError::Endpoint,
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::endpoints::auth_flow::HoneySubmitPasswordError;
+
+    fn error_response(code: u32, kind: &str) -> WsResponseError {
+        WsResponseError {
+            method: HoneyEndpointMethodCode::SubmitPassword as u32,
+            code,
+            seq: 1,
+            log_id: String::new(),
+            params: serde_json::json!({ "kind": kind, "message": "msg" }),
+        }
+    }
+
+    #[test]
+    fn decodes_declared_error_kind() {
+        let err = decode_endpoint_error::<HoneySubmitPasswordError>(&error_response(100403, "AccessDenied"));
+
+        assert!(matches!(
+            err,
+            HoneyIdEndpointError::Endpoint(HoneySubmitPasswordError::AccessDenied)
+        ));
+    }
+
+    #[test]
+    fn keeps_undeclared_error_kind_as_other() {
+        let err = decode_endpoint_error::<HoneySubmitPasswordError>(&error_response(100500, "InternalError"));
+
+        let HoneyIdEndpointError::Other(err) = err else {
+            panic!("expected undeclared error, got {err:?}");
+        };
+        assert_eq!(err.code, ErrorCode::new(100500));
+    }
+
+    fn immediate(seq: u32) -> Value {
+        serde_json::json!({ "type": "Immediate", "method": 1, "seq": seq, "params": seq })
+    }
+
+    fn driver() -> (
+        ConnectionDriver,
+        oneshot::Receiver<HoneyIdResult<HoneyIdServerFrame>>,
+        broadcast::Receiver<HoneyIdServerFrame>,
+    ) {
+        let (_commands, command_rx) = mpsc::unbounded_channel();
+        let (handshake_tx, handshake_rx) = oneshot::channel();
+        let (server_messages, pushed) = broadcast::channel(SERVER_MESSAGES_CAPACITY);
+        let driver = ConnectionDriver {
+            commands: command_rx,
+            pending: HashMap::new(),
+            cancelled: CancelledCalls::default(),
+            handshake: Some(handshake_tx),
+            server_messages,
+        };
+
+        (driver, handshake_rx, pushed)
+    }
+
+    #[test]
+    fn routes_out_of_order_responses_by_seq() {
+        let (mut driver, mut handshake_rx, mut pushed) = driver();
+        let (first_tx, mut first_rx) = oneshot::channel();
+        let (second_tx, mut second_rx) = oneshot::channel();
+        driver.pending.insert(1, first_tx);
+        driver.pending.insert(2, second_tx);
+
+        assert!(driver.dispatch(immediate(0)));
+        assert!(driver.dispatch(immediate(2)));
+        assert!(driver.dispatch(immediate(1)));
+        assert!(driver.dispatch(immediate(3)));
+
+        let seq_of = |frame: HoneyIdServerFrame| match frame {
+            WsResponseGeneric::Immediate(resp) => resp.seq,
+            other => panic!("unexpected frame {other:?}"),
+        };
+        assert_eq!(seq_of(handshake_rx.try_recv().unwrap().unwrap()), 0);
+        assert_eq!(seq_of(first_rx.try_recv().unwrap().unwrap()), 1);
+        assert_eq!(seq_of(second_rx.try_recv().unwrap().unwrap()), 2);
+        assert_eq!(seq_of(pushed.try_recv().unwrap()), 3);
+        assert!(driver.pending.is_empty());
+    }
+
+    #[test]
+    fn discards_late_response_to_cancelled_call() {
+        let (mut driver, _handshake_rx, mut pushed) = driver();
+        let (reply, _response) = oneshot::channel();
+        driver.pending.insert(1, reply);
+
+        driver.cancel(1);
+        assert!(driver.dispatch(immediate(1)));
+
+        assert!(driver.pending.is_empty());
+        assert!(driver.cancelled.seqs.is_empty());
+        assert!(pushed.try_recv().is_err());
+    }
+
+    #[test]
+    fn forgets_oldest_cancelled_calls() {
+        let mut cancelled = CancelledCalls::default();
+        for seq in 1..=MAX_CANCELLED_CALLS as u32 + 1 {
+            cancelled.insert(seq);
+        }
+
+        assert_eq!(cancelled.seqs.len(), MAX_CANCELLED_CALLS);
+        assert!(!cancelled.remove(1));
+        assert!(cancelled.remove(2));
+        assert!(cancelled.order.len() <= MAX_CANCELLED_CALLS);
+    }
+
+    #[tokio::test]
+    async fn times_out_connecting_to_stalled_server() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = Url::parse(&format!("ws://{}", listener.local_addr().unwrap())).unwrap();
+        // Accepts the TCP connection but never answers the upgrade.
+        std::thread::spawn(move || {
+            let _stream = listener.accept();
+            std::thread::sleep(Duration::from_secs(5));
+        });
+
+        let timeout = Duration::from_millis(100);
+        let err = HoneyIdConnection::connect_with_timeout(&addr, None, timeout)
+            .await
+            .unwrap_err();
+
+        assert!(err.is_timeout(), "expected timeout, got {err:?}");
+    }
+
+    #[tokio::test]
+    #[allow(deprecated)]
+    async fn receives_deprecated_responses_in_send_order() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = Url::parse(&format!("ws://{}", listener.local_addr().unwrap())).unwrap();
+        // Answers both calls in reverse order, echoing their params.
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            let mut requests = Vec::new();
+            while requests.len() < 2 {
+                if let Some(Ok(Message::Text(text))) = ws.next().await {
+                    requests.push(serde_json::from_str::<WsRequestGeneric<Value>>(&text).unwrap());
+                }
+            }
+            for req in requests.into_iter().rev() {
+                let resp = serde_json::json!({
+                    "type": "Immediate", "method": req.method, "seq": req.seq, "params": req.params,
+                });
+                ws.send(Message::text(resp.to_string())).await.unwrap();
+            }
+            let _ = ws.next().await;
+        });
+
+        let conn = HoneyIdConnection::connect(&addr, None).await.unwrap();
+        conn.send_request_raw(1, 10).await.unwrap();
+        conn.send_request_raw(1, 20).await.unwrap();
+
+        assert_eq!(conn.receive_response::<u32>().await.unwrap(), 10);
+        assert_eq!(conn.receive_response::<u32>().await.unwrap(), 20);
+        assert!(conn.receive_response::<u32>().await.is_err());
+    }
 }
@@ -0,0 +1,158 @@
+//! Definition of [`HoneyIdPlatformClient`].
+//!
+//! Typed client for the `platform` endpoints of `honey.id`, which are only
+//! available to a connection authorized with the platform API key.
+
+use secrecy::{ExposeSecret, SecretString};
+use std::time::Duration;
+use url::Url;
+
+use crate::client::HoneyIdClient;
+use crate::client::honeyid_ws_conn::{DEFAULT_REQUEST_TIMEOUT, HoneyIdConnection};
+use crate::endpoints::platform::{
+    BanUserError, BanUserRequest, BanUserResponse, CreateAppConfigError, CreateAppConfigRequest,
+    CreateAppConfigResponse, DeleteAppConfigError, DeleteAppConfigRequest, DeleteAppConfigResponse, DeleteUserError,
+    DeleteUserRequest, DeleteUserResponse, EditAppConfigError, EditAppConfigRequest, EditAppConfigResponse,
+    GetAppSecurityRulesError, GetAppSecurityRulesRequest, GetAppSecurityRulesResponse, PlatformConnectError,
+    PlatformConnectRequest, SetLogLevelError, SetLogLevelRequest, SetLogLevelResponse, UnbanUserError,
+    UnbanUserRequest, UnbanUserResponse,
+};
+use crate::enums::HoneyEndpointMethodCode;
+use crate::types::error::{HoneyEndpointRequest, HoneyIdEndpointResult};
+
+/// Client for the `honey.id` platform API.
+///
+/// Holds a single connection authorized via [`PlatformConnect`], and exposes
+/// one method per platform endpoint. Each method returns the endpoint's
+/// generated error enum when the Auth server rejects the call.
+///
+/// Calls may run concurrently, so a single client can be shared across tasks
+/// behind an [`Arc`](std::sync::Arc).
+///
+/// [`PlatformConnect`]: crate::endpoints::platform::PlatformConnectRequest
+#[derive(Debug)]
+pub struct HoneyIdPlatformClient {
+    conn: HoneyIdConnection,
+}
+
+impl HoneyIdPlatformClient {
+    /// Same as [`connect_with_timeout`] with [`DEFAULT_REQUEST_TIMEOUT`].
+    ///
+    /// [`connect_with_timeout`]: Self::connect_with_timeout
+    pub async fn connect(
+        addr: &Url,
+        platform_api_key: &SecretString,
+    ) -> HoneyIdEndpointResult<Self, PlatformConnectError> {
+        Self::connect_with_timeout(addr, platform_api_key, DEFAULT_REQUEST_TIMEOUT).await
+    }
+
+    /// Connects to `honey.id` at `addr` and authorizes the connection with
+    /// `platform_api_key` using the [`PlatformConnect`] handshake.
+    ///
+    /// `timeout` bounds the connect, the handshake and every later call, e.g.
+    /// [`HoneyIdConfig::request_timeout`](crate::HoneyIdConfig::request_timeout).
+    ///
+    /// [`PlatformConnect`]: crate::endpoints::platform::PlatformConnectRequest
+    pub async fn connect_with_timeout(
+        addr: &Url,
+        platform_api_key: &SecretString,
+        timeout: Duration,
+    ) -> HoneyIdEndpointResult<Self, PlatformConnectError> {
+        let header = HoneyIdClient::connect_header(
+            HoneyEndpointMethodCode::PlatformConnect,
+            &[platform_api_key.expose_secret()],
+        )?;
+        let conn = HoneyIdConnection::connect_with_timeout(addr, Some(&header), timeout).await?;
+
+        conn.receive_handshake::<PlatformConnectRequest>().await?;
+
+        Ok(Self { conn })
+    }
+
+    /// Calls [`CreateAppConfig`] endpoint.
+    ///
+    /// [`CreateAppConfig`]: CreateAppConfigRequest
+    pub async fn create_app_config(
+        &self,
+        req: CreateAppConfigRequest,
+    ) -> HoneyIdEndpointResult<CreateAppConfigResponse, CreateAppConfigError> {
+        self.call(req).await
+    }
+
+    /// Calls [`BanUser`] endpoint.
+    ///
+    /// [`BanUser`]: BanUserRequest
+    pub async fn ban_user(&self, req: BanUserRequest) -> HoneyIdEndpointResult<BanUserResponse, BanUserError> {
+        self.call(req).await
+    }
+
+    /// Calls [`UnbanUser`] endpoint.
+    ///
+    /// [`UnbanUser`]: UnbanUserRequest
+    pub async fn unban_user(&self, req: UnbanUserRequest) -> HoneyIdEndpointResult<UnbanUserResponse, UnbanUserError> {
+        self.call(req).await
+    }
+
+    /// Calls [`DeleteUser`] endpoint.
+    ///
+    /// [`DeleteUser`]: DeleteUserRequest
+    pub async fn delete_user(
+        &self,
+        req: DeleteUserRequest,
+    ) -> HoneyIdEndpointResult<DeleteUserResponse, DeleteUserError> {
+        self.call(req).await
+    }
+
+    /// Calls [`DeleteAppConfig`] endpoint.
+    ///
+    /// [`DeleteAppConfig`]: DeleteAppConfigRequest
+    pub async fn delete_app_config(
+        &self,
+        req: DeleteAppConfigRequest,
+    ) -> HoneyIdEndpointResult<DeleteAppConfigResponse, DeleteAppConfigError> {
+        self.call(req).await
+    }
+
+    /// Calls [`EditAppConfig`] endpoint.
+    ///
+    /// [`EditAppConfig`]: EditAppConfigRequest
+    pub async fn edit_app_config(
+        &self,
+        req: EditAppConfigRequest,
+    ) -> HoneyIdEndpointResult<EditAppConfigResponse, EditAppConfigError> {
+        self.call(req).await
+    }
+
+    /// Calls [`GetAppSecurityRules`] endpoint.
+    ///
+    /// [`GetAppSecurityRules`]: GetAppSecurityRulesRequest
+    pub async fn get_app_security_rules(
+        &self,
+        req: GetAppSecurityRulesRequest,
+    ) -> HoneyIdEndpointResult<GetAppSecurityRulesResponse, GetAppSecurityRulesError> {
+        self.call(req).await
+    }
+
+    /// Calls [`SetLogLevel`] endpoint.
+    ///
+    /// [`SetLogLevel`]: SetLogLevelRequest
+    pub async fn set_log_level(
+        &self,
+        req: SetLogLevelRequest,
+    ) -> HoneyIdEndpointResult<SetLogLevelResponse, SetLogLevelError> {
+        self.call(req).await
+    }
+
+    /// Returns the underlying [`HoneyIdConnection`], e.g. to call endpoints
+    /// that are not defined within this project.
+    pub fn connection(&self) -> &HoneyIdConnection {
+        &self.conn
+    }
+
+    async fn call<Req>(&self, req: Req) -> HoneyIdEndpointResult<Req::Response, Req::Error>
+    where
+        Req: HoneyEndpointRequest,
+    {
+        self.conn.request_typed(req).await
+    }
+}
@@ -0,0 +1,389 @@
+//! Definition of [`ReconnectingHoneyIdConnection`].
+//!
+//! Long-lived connection to `honey.id` that reconnects with exponential
+//! backoff and replays its connect handshake whenever the socket drops.
+
+use endpoint_libs::libs::error_code::ErrorCode;
+use secrecy::{ExposeSecret, SecretString};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use url::Url;
+
+use crate::client::HoneyIdClient;
+use crate::client::honeyid_ws_conn::{DEFAULT_REQUEST_TIMEOUT, HoneyIdConnection, connection_closed};
+use crate::credentials::AccessToken;
+use crate::endpoints::connect::HoneyAuthorizedConnectRequest;
+use crate::endpoints::platform::PlatformConnectRequest;
+use crate::enums::HoneyEndpointMethodCode;
+use crate::types::error::{
+    HoneyEndpointRequest, HoneyIdEndpointError, HoneyIdEndpointResult, HoneyIdError, HoneyIdResult,
+};
+
+/// Connect handshake replayed by [`ReconnectingHoneyIdConnection`] on every
+/// (re)connect.
+#[derive(Debug, Clone)]
+pub enum HoneyIdHandshake {
+    /// [`PublicConnect`](crate::endpoints::connect::HoneyPublicConnectRequest).
+    Public,
+    /// [`PlatformConnect`](PlatformConnectRequest) with the platform API key.
+    PlatformApiKey(SecretString),
+    /// [`AuthorizedConnect`](HoneyAuthorizedConnectRequest) with a `User`'s
+    /// access token.
+    AccessToken(AccessToken),
+}
+
+impl HoneyIdHandshake {
+    /// Opens a new connection to `addr` and performs the handshake on it.
+    ///
+    /// `timeout` bounds the connect, the handshake and every later call on
+    /// the connection.
+    pub async fn connect(&self, addr: &Url, timeout: Duration) -> HoneyIdResult<HoneyIdConnection> {
+        self.try_connect(addr, timeout)
+            .await
+            .map_err(HandshakeError::into_inner)
+    }
+
+    async fn try_connect(&self, addr: &Url, timeout: Duration) -> Result<HoneyIdConnection, HandshakeError> {
+        match self {
+            Self::Public => {
+                let header = HoneyIdClient::connect_header(HoneyEndpointMethodCode::PublicConnect, &[])?;
+                Ok(HoneyIdConnection::connect_with_timeout(addr, Some(&header), timeout).await?)
+            }
+            Self::PlatformApiKey(key) => {
+                let header =
+                    HoneyIdClient::connect_header(HoneyEndpointMethodCode::PlatformConnect, &[key.expose_secret()])?;
+                let conn = HoneyIdConnection::connect_with_timeout(addr, Some(&header), timeout).await?;
+                conn.receive_handshake::<PlatformConnectRequest>().await?;
+                Ok(conn)
+            }
+            Self::AccessToken(token) => {
+                let header = HoneyIdClient::connect_header(
+                    HoneyEndpointMethodCode::AuthorizedConnect,
+                    &[&token.expose_secret().to_string()],
+                )?;
+                let conn = HoneyIdConnection::connect_with_timeout(addr, Some(&header), timeout).await?;
+                conn.receive_handshake::<HoneyAuthorizedConnectRequest>().await?;
+                Ok(conn)
+            }
+        }
+    }
+}
+
+/// Failure of [`HoneyIdHandshake::try_connect`].
+enum HandshakeError {
+    /// The Auth server rejected the handshake, retrying can't succeed.
+    Rejected(HoneyIdError),
+    /// Connecting failed, e.g. the Auth server is unreachable.
+    Failed(HoneyIdError),
+}
+
+impl HandshakeError {
+    fn into_inner(self) -> HoneyIdError {
+        match self {
+            Self::Rejected(err) | Self::Failed(err) => err,
+        }
+    }
+}
+
+impl From<HoneyIdError> for HandshakeError {
+    fn from(err: HoneyIdError) -> Self {
+        Self::Failed(err)
+    }
+}
+
+impl<E: Debug> From<HoneyIdEndpointError<E>> for HandshakeError {
+    fn from(err: HoneyIdEndpointError<E>) -> Self {
+        match err {
+            HoneyIdEndpointError::Endpoint(err) => {
+                Self::Rejected(HoneyIdError::new(ErrorCode::UNAUTHORIZED, format!("{err:?}")))
+            }
+            HoneyIdEndpointError::Other(err) => Self::Failed(err),
+        }
+    }
+}
+
+/// Backoff applied by [`ReconnectingHoneyIdConnection`] between connect
+/// attempts.
+///
+/// The delay doubles with every failed attempt, starting at
+/// `initial_backoff` and capped at `max_backoff`. A random jitter of up to
+/// half the delay is subtracted, so that many clients don't reconnect in
+/// lockstep.
+#[derive(Debug, Clone, Copy)]
+pub struct ReconnectPolicy {
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Consecutive failed attempts after which the connection gives up and
+    /// moves to [`HoneyIdConnectionState::Closed`]. `None` retries forever.
+    pub max_attempts: Option<u32>,
+    /// Timeout of each connect attempt and of every call, usually
+    /// [`HoneyIdConfig::request_timeout`](crate::HoneyIdConfig::request_timeout).
+    /// Calls made while disconnected fail once it elapses without a
+    /// successful handshake.
+    pub request_timeout: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            max_attempts: None,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Delay before the connect attempt following `failed_attempts`
+    /// consecutive failures.
+    fn backoff(&self, failed_attempts: u32) -> Duration {
+        let delay = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(failed_attempts))
+            .min(self.max_backoff);
+        let half = delay / 2;
+        let jitter = fastrand::u64(0..=half.as_millis() as u64);
+
+        half + Duration::from_millis(jitter)
+    }
+}
+
+/// State of a [`ReconnectingHoneyIdConnection`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HoneyIdConnectionState {
+    /// First connect attempt is in progress.
+    Connecting,
+    /// The handshake succeeded and calls can be made.
+    Connected,
+    /// The socket dropped or a connect attempt failed; `attempt` numbers the
+    /// next connect attempt since the last successful one.
+    Reconnecting { attempt: u32 },
+    /// The Auth server rejected the handshake or
+    /// [`ReconnectPolicy::max_attempts`] was exhausted, no further attempts
+    /// will be made.
+    Closed,
+}
+
+/// Connection to `honey.id` that transparently reconnects.
+///
+/// A background task keeps the connection open, replaying the
+/// [`HoneyIdHandshake`] after every reconnect, and publishes its
+/// [`HoneyIdConnectionState`] through [`state`](Self::state). Calls made while
+/// disconnected wait up to [`ReconnectPolicy::request_timeout`] for the next
+/// successful handshake, and fail once the connection is
+/// [`Closed`](HoneyIdConnectionState::Closed). Calls in flight when the socket
+/// drops fail with a closed connection error and are not replayed.
+///
+/// The background task stops once this value is dropped.
+#[derive(Debug)]
+pub struct ReconnectingHoneyIdConnection {
+    conn: watch::Receiver<Option<Arc<HoneyIdConnection>>>,
+    state: watch::Receiver<HoneyIdConnectionState>,
+    request_timeout: Duration,
+    supervisor: JoinHandle<()>,
+}
+
+impl ReconnectingHoneyIdConnection {
+    /// Starts connecting to `addr` in the background.
+    ///
+    /// Returns immediately; use [`state`](Self::state) to wait for
+    /// readiness.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a Tokio runtime, which runs the background
+    /// task.
+    pub fn connect(addr: &Url, handshake: HoneyIdHandshake, policy: ReconnectPolicy) -> Self {
+        let (conn_tx, conn) = watch::channel(None);
+        let (state_tx, state) = watch::channel(HoneyIdConnectionState::Connecting);
+        let supervisor = tokio::spawn(supervise(addr.clone(), handshake, policy, conn_tx, state_tx));
+
+        Self {
+            conn,
+            state,
+            request_timeout: policy.request_timeout,
+            supervisor,
+        }
+    }
+
+    /// Subscribes to state changes of the connection.
+    pub fn state(&self) -> watch::Receiver<HoneyIdConnectionState> {
+        self.state.clone()
+    }
+
+    /// Returns the current connection, waiting up to
+    /// [`ReconnectPolicy::request_timeout`] for the handshake to succeed if
+    /// the socket is being reconnected.
+    ///
+    /// Fails with a closed connection error once the connection is
+    /// [`Closed`](HoneyIdConnectionState::Closed).
+    pub async fn connection(&self) -> HoneyIdResult<Arc<HoneyIdConnection>> {
+        let mut conn = self.conn.clone();
+        let mut state = self.state.clone();
+        let connected = async {
+            loop {
+                if let Some(current) = conn.borrow_and_update().clone() {
+                    return Ok(current);
+                }
+                if *state.borrow_and_update() == HoneyIdConnectionState::Closed {
+                    return Err(connection_closed());
+                }
+                tokio::select! {
+                    changed = conn.changed() => changed.map_err(|_| connection_closed())?,
+                    changed = state.changed() => changed.map_err(|_| connection_closed())?,
+                }
+            }
+        };
+
+        tokio::time::timeout(self.request_timeout, connected)
+            .await
+            .map_err(|_| HoneyIdError::timeout(self.request_timeout))?
+    }
+
+    /// See [`HoneyIdConnection::request`].
+    pub async fn request<T, P>(&self, method: HoneyEndpointMethodCode, params: P) -> eyre::Result<T>
+    where
+        T: DeserializeOwned,
+        P: Serialize,
+    {
+        self.connection().await?.request(method, params).await
+    }
+
+    /// See [`HoneyIdConnection::request_raw`].
+    pub async fn request_raw<T, P>(&self, method: u32, params: P) -> eyre::Result<T>
+    where
+        T: DeserializeOwned,
+        P: Serialize,
+    {
+        self.connection().await?.request_raw(method, params).await
+    }
+
+    /// See [`HoneyIdConnection::request_typed`].
+    pub async fn request_typed<Req>(&self, req: Req) -> HoneyIdEndpointResult<Req::Response, Req::Error>
+    where
+        Req: HoneyEndpointRequest,
+    {
+        self.connection().await?.request_typed(req).await
+    }
+}
+
+impl Drop for ReconnectingHoneyIdConnection {
+    fn drop(&mut self) {
+        self.supervisor.abort();
+    }
+}
+
+async fn supervise(
+    addr: Url,
+    handshake: HoneyIdHandshake,
+    policy: ReconnectPolicy,
+    conn_tx: watch::Sender<Option<Arc<HoneyIdConnection>>>,
+    state_tx: watch::Sender<HoneyIdConnectionState>,
+) {
+    let mut failed_attempts = 0;
+    loop {
+        match handshake.try_connect(&addr, policy.request_timeout).await {
+            Ok(conn) => {
+                failed_attempts = 0;
+                let conn = Arc::new(conn);
+                conn_tx.send_replace(Some(conn.clone()));
+                state_tx.send_replace(HoneyIdConnectionState::Connected);
+                tracing::debug!(%addr, "Connected to honey.id");
+
+                conn.closed().await;
+                conn_tx.send_replace(None);
+                tracing::warn!(%addr, "Connection to honey.id lost");
+            }
+            Err(HandshakeError::Rejected(err)) => {
+                tracing::error!(%addr, %err, "honey.id rejected the handshake, not reconnecting");
+                state_tx.send_replace(HoneyIdConnectionState::Closed);
+                return;
+            }
+            Err(HandshakeError::Failed(err)) => {
+                failed_attempts += 1;
+                tracing::warn!(%addr, %err, attempt = failed_attempts, "Failed to connect to honey.id");
+                if policy.max_attempts.is_some_and(|max| failed_attempts >= max) {
+                    state_tx.send_replace(HoneyIdConnectionState::Closed);
+                    return;
+                }
+            }
+        }
+
+        state_tx.send_replace(HoneyIdConnectionState::Reconnecting {
+            attempt: failed_attempts + 1,
+        });
+        tokio::time::sleep(policy.backoff(failed_attempts)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_grows_with_jitter_up_to_max() {
+        let policy = ReconnectPolicy {
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+            max_attempts: None,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+        };
+
+        for (failed_attempts, full) in [(0, 100), (1, 200), (2, 400), (3, 800), (4, 1000), (40, 1000)] {
+            let delay = policy.backoff(failed_attempts).as_millis();
+            assert!(
+                (full / 2..=full).contains(&delay),
+                "attempt {failed_attempts}: {delay}ms not within {full}ms backoff"
+            );
+        }
+    }
+
+    fn fast_policy() -> ReconnectPolicy {
+        ReconnectPolicy {
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(50),
+            max_attempts: None,
+            request_timeout: Duration::from_millis(500),
+        }
+    }
+
+    #[tokio::test]
+    async fn closes_when_handshake_is_rejected() {
+        let server = crate::testing::MockHoneyIdServer::start(SecretString::from("platform-key"))
+            .await
+            .unwrap();
+
+        let handshake = HoneyIdHandshake::PlatformApiKey(SecretString::from("wrong-key"));
+        let conn = ReconnectingHoneyIdConnection::connect(server.addr(), handshake, fast_policy());
+        let mut state = conn.state();
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            state.wait_for(|state| *state == HoneyIdConnectionState::Closed),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        let err = conn.connection().await.unwrap_err();
+        assert!(!err.is_timeout(), "expected closed connection, got {err:?}");
+    }
+
+    #[tokio::test]
+    async fn times_out_waiting_for_connection() {
+        // Nothing listens on the port once the listener is dropped.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = Url::parse(&format!("ws://{}", listener.local_addr().unwrap())).unwrap();
+        drop(listener);
+
+        let conn = ReconnectingHoneyIdConnection::connect(&addr, HoneyIdHandshake::Public, fast_policy());
+        let err = conn.connection().await.unwrap_err();
+
+        assert!(err.is_timeout(), "expected timeout, got {err:?}");
+    }
+}
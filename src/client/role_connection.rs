@@ -0,0 +1,163 @@
+//! Definition of [`HoneyIdRoleConnection`].
+//!
+//! Connection returned by the connect helpers of
+//! [`HoneyIdClient`](crate::HoneyIdClient), typed by the connect endpoint
+//! that authorized it, so that only endpoints allowed for that role can be
+//! called on it.
+
+use std::marker::PhantomData;
+
+use crate::client::honeyid_ws_conn::HoneyIdConnection;
+use crate::endpoints::auth_flow::{HoneySignupRequest, HoneySubmitPasswordRequest, HoneySubmitUsernameRequest};
+use crate::endpoints::callback::{
+    HoneyReceiveTokenRequest, HoneyReceiveUserDeletedRequest, HoneyReceiveUserInfoRequest,
+    HoneyReceiveUserStatusChangedRequest, HoneyValidateTokenRequest,
+};
+use crate::enums::HoneyUserRole;
+use crate::types::error::{HoneyEndpointRequest, HoneyIdEndpointResult};
+
+/// Connect endpoint a [`HoneyIdRoleConnection`] was authorized with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, derive_more::Display)]
+pub enum HoneyIdConnectionRole {
+    /// [`PublicConnect`](crate::endpoints::connect::HoneyPublicConnectRequest).
+    #[display("public")]
+    Public,
+    /// [`AuthorizedConnect`](crate::endpoints::connect::HoneyAuthorizedConnectRequest),
+    /// acting as a signed in `User`.
+    #[display("authorized")]
+    Authorized,
+    /// [`ApiKeyConnect`](crate::endpoints::connect::HoneyApiKeyConnectRequest).
+    #[display("api key")]
+    ApiKey,
+}
+
+impl HoneyIdConnectionRole {
+    /// Returns the [`HoneyUserRole`] granted by the connect endpoint, `None`
+    /// for [`Authorized`](Self::Authorized) connections, which get the roles
+    /// of their `User`.
+    pub fn user_role(self) -> Option<HoneyUserRole> {
+        match self {
+            Self::Public => Some(HoneyUserRole::Public),
+            Self::Authorized => None,
+            Self::ApiKey => Some(HoneyUserRole::AppApiKey),
+        }
+    }
+}
+
+/// Type-level [`HoneyIdConnectionRole`]s of [`HoneyIdRoleConnection`].
+pub mod roles {
+    use super::HoneyIdConnectionRole;
+
+    mod sealed {
+        pub trait Sealed {}
+    }
+
+    /// Connect endpoint a [`HoneyIdRoleConnection`](super::HoneyIdRoleConnection)
+    /// was authorized with, known at compile time.
+    pub trait ConnectionRole: sealed::Sealed + Send + Sync + 'static {
+        const ROLE: HoneyIdConnectionRole;
+    }
+
+    macro_rules! connection_role {
+        ($($(#[$meta:meta])* $name:ident),* $(,)?) => {
+            $(
+                $(#[$meta])*
+                #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+                pub enum $name {}
+
+                impl sealed::Sealed for $name {}
+
+                impl ConnectionRole for $name {
+                    const ROLE: HoneyIdConnectionRole = HoneyIdConnectionRole::$name;
+                }
+            )*
+        };
+    }
+
+    connection_role!(
+        /// See [`HoneyIdConnectionRole::Public`].
+        Public,
+        /// See [`HoneyIdConnectionRole::Authorized`].
+        Authorized,
+        /// See [`HoneyIdConnectionRole::ApiKey`].
+        ApiKey,
+    );
+}
+
+use roles::ConnectionRole;
+
+/// Request of an endpoint that connections with role `R` are allowed to call,
+/// per the roles declared by its schema.
+pub trait RoleRequest<R: ConnectionRole>: HoneyEndpointRequest {}
+
+macro_rules! impl_role_request {
+    ($role:ty => $($req:ty),* $(,)?) => {
+        $(
+            impl RoleRequest<$role> for $req {}
+        )*
+    };
+}
+
+impl_role_request!(roles::Public => HoneySignupRequest, HoneySubmitUsernameRequest, HoneySubmitPasswordRequest);
+impl_role_request!(
+    roles::ApiKey => HoneyReceiveTokenRequest,
+    HoneyReceiveUserInfoRequest,
+    HoneyReceiveUserDeletedRequest,
+    HoneyValidateTokenRequest,
+    HoneyReceiveUserStatusChangedRequest,
+);
+
+/// [`HoneyIdConnection`] authorized with the connect endpoint of role `R`.
+///
+/// [`request_typed`](Self::request_typed) only accepts requests of endpoints
+/// allowed for `R`, e.g. [`SubmitPassword`] can't be called on an
+/// [`ApiKey`](roles::ApiKey) connection:
+///
+/// ```compile_fail
+/// # async fn call(
+/// #     conn: honey_id_types::HoneyIdRoleConnection<honey_id_types::roles::ApiKey>,
+/// #     req: honey_id_types::endpoints::auth_flow::HoneySubmitPasswordRequest,
+/// # ) {
+/// conn.request_typed(req).await;
+/// # }
+/// ```
+///
+/// [`SubmitPassword`]: crate::endpoints::auth_flow::HoneySubmitPasswordRequest
+#[derive(Debug)]
+pub struct HoneyIdRoleConnection<R: ConnectionRole> {
+    conn: HoneyIdConnection,
+    _role: PhantomData<R>,
+}
+
+impl<R: ConnectionRole> HoneyIdRoleConnection<R> {
+    pub(crate) fn new(conn: HoneyIdConnection) -> Self {
+        Self {
+            conn,
+            _role: PhantomData,
+        }
+    }
+
+    pub fn role(&self) -> HoneyIdConnectionRole {
+        R::ROLE
+    }
+
+    /// See [`HoneyIdConnection::request_typed`].
+    pub async fn request_typed<Req>(&self, req: Req) -> HoneyIdEndpointResult<Req::Response, Req::Error>
+    where
+        Req: RoleRequest<R>,
+    {
+        self.conn.request_typed(req).await
+    }
+
+    /// Returns the underlying [`HoneyIdConnection`], which isn't restricted
+    /// to the endpoints of `R`.
+    pub fn connection(&self) -> &HoneyIdConnection {
+        &self.conn
+    }
+
+    /// Consumes this connection, returning the underlying
+    /// [`HoneyIdConnection`], which isn't restricted to the endpoints of `R`.
+    pub fn into_connection(self) -> HoneyIdConnection {
+        self.conn
+    }
+}
@@ -1,9 +1,11 @@
 use psc_nanoid::{Nanoid, alphabet::Base62Alphabet};
-use secrecy::SecretString;
 use serde::Deserialize;
 use smart_default::SmartDefault;
+use std::time::Duration;
 use url::Url;
 
+use crate::credentials::AppApiKey;
+
 /// Configuration for `honey.id` client.
 ///
 /// Implements [`Deserialize`] so could be easily used as entry for other
@@ -25,9 +27,36 @@ pub struct HoneyIdConfig {
     /// `honey.id` UI.
     ///
     /// Will be used by `Auth` for callback endpoints authorization.
-    pub auth_api_key: SecretString,
+    pub auth_api_key: AppApiKey,
+
+    /// Additional `Auth` API keys accepted next to
+    /// [`auth_api_key`](Self::auth_api_key).
+    ///
+    /// Allows rotating the key without downtime: add the new key here,
+    /// switch `Auth` over to it, then promote it to `auth_api_key` once the
+    /// old key stops being matched.
+    #[serde(default)]
+    pub secondary_auth_api_keys: Vec<AppApiKey>,
 
     /// The Public ID of the user that will be the first "Admin" within the app.
+    /// Requires a `UserStorage` implementing `assign_admin_role`.
     #[serde(default)]
     pub admin_pub_id: Option<Nanoid<16, Base62Alphabet>>,
+
+    /// Timeout in milliseconds of calls to `honey.id`, after which they fail
+    /// with [`HoneyIdError::timeout`]. Can be overridden per call.
+    ///
+    /// Set default to 30 seconds.
+    ///
+    /// [`HoneyIdError::timeout`]: crate::error::HoneyIdError::timeout
+    #[default(30_000)]
+    pub request_timeout_ms: u64,
+}
+
+impl HoneyIdConfig {
+    /// Returns [`request_timeout_ms`](Self::request_timeout_ms) as a
+    /// [`Duration`].
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_millis(self.request_timeout_ms)
+    }
 }
@@ -6,5 +6,8 @@ pub mod auth_to_app;
 // Defines all handlers that are for User (FE) to App (BE) communication
 pub mod user_to_app;
 
+// Wires all handlers on an endpoint-libs WebSocket server
+pub mod integration;
+
 // Defines some convenience utilities to prevent duplicated code on every app backend that uses the honey client
 pub mod convenience_utils;
@@ -1,28 +1,44 @@
 //! Defines all handlers that are for Auth (BE) to App (BE) communication
 use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
 use endpoint_libs::libs::handler::{HandlerError, RequestHandler, Response};
 use endpoint_libs::libs::toolbox::{ArcToolbox, CustomError, RequestContext};
+use endpoint_libs::libs::utils::get_time_milliseconds;
 use endpoint_libs::libs::ws::{SubAuthController, WsConnection};
 use futures::FutureExt;
 use futures::future::LocalBoxFuture;
-use uuid::Uuid;
 
 use crate::client::{ApiKeyError, HoneyIdClient};
 use crate::endpoints::callback::{
     HoneyReceiveTokenError, HoneyReceiveTokenRequest, HoneyReceiveTokenResponse, HoneyReceiveUserDeletedRequest,
     HoneyReceiveUserDeletedResponse, HoneyReceiveUserInfoError, HoneyReceiveUserInfoRequest,
-    HoneyReceiveUserInfoResponse, HoneyValidateTokenError, HoneyValidateTokenRequest, HoneyValidateTokenResponse,
+    HoneyReceiveUserInfoResponse, HoneyReceiveUserStatusChangedRequest, HoneyReceiveUserStatusChangedResponse,
+    HoneyValidateTokenError, HoneyValidateTokenRequest, HoneyValidateTokenResponse,
 };
 use crate::endpoints::connect::{HoneyApiKeyConnectError, HoneyApiKeyConnectRequest, HoneyApiKeyConnectResponse};
+use crate::enums::AuthUserStatus;
+use crate::handlers::convenience_utils::audit::{AuthAuditEventKind, AuthAuditSink, audit};
+use crate::handlers::convenience_utils::rate_limiting::{
+    AuthRateLimiter, RateLimitKey, check_attempt, record_failed_attempt,
+};
+use crate::handlers::convenience_utils::session_management::SessionRegistry;
 use crate::handlers::convenience_utils::token_management::TokenStorage;
-use crate::handlers::convenience_utils::user_management::{CreateUserInfo, DeleteUserInfo, UserStorage};
-use crate::types::id_entities::UserPublicId;
+use crate::handlers::convenience_utils::user_management::{
+    CreateUserInfo, DeleteUserInfo, UserStorage, UserUpsertOutcome,
+};
+use crate::types::id_entities::{AppPublicId, UserPublicId};
 
 pub struct MethodApiKeyConnect {
     pub honey_id_client: Arc<HoneyIdClient>,
     pub user_storage: Arc<dyn UserStorage + Send + Sync>,
+    /// Limiter of failed attempts, keyed by peer address and API key prefix.
+    /// Throttled attempts are answered with
+    /// [`TooManyRequests`](HoneyApiKeyConnectError::TooManyRequests).
+    pub rate_limiter: Option<Arc<dyn AuthRateLimiter>>,
+    /// Receiver of audit events of this handler.
+    pub audit_sink: Option<Arc<dyn AuthAuditSink>>,
 }
 
 #[async_trait(?Send)]
@@ -38,9 +54,21 @@ impl SubAuthController for MethodApiKeyConnect {
         conn: Arc<WsConnection>,
     ) -> LocalBoxFuture<'static, Response<Self::Request, Self::Error>> {
         async move {
+            let rate_limiter = self.rate_limiter.as_deref();
+            let attempt_keys = RateLimitKey::for_attempt(&conn.peer, req.appApiKey.expose_secret());
+            check_attempt(rate_limiter, &attempt_keys)
+                .map_err(|_| HandlerError::Public(HoneyApiKeyConnectError::TooManyRequests))?;
+
             self.honey_id_client
-                .validate_auth_api_key(&req.appApiKey)
+                .validate_auth_api_key(req.appApiKey.expose_secret())
                 .map_err(|err| {
+                    record_failed_attempt(rate_limiter, &attempt_keys);
+                    audit(
+                        self.audit_sink.as_deref(),
+                        AuthAuditEventKind::ApiKeyFailure {
+                            peer_addr: conn.peer.ip_addr(),
+                        },
+                    );
                     tracing::error!(
                         error = %err,
                         "Failed to validate Auth API key due to error"
@@ -62,6 +90,16 @@ impl SubAuthController for MethodApiKeyConnect {
 pub struct MethodReceiveToken {
     pub token_storage: Arc<dyn TokenStorage + Sync + Send>,
     pub user_storage: Arc<dyn UserStorage + Send + Sync>,
+    /// How long received tokens stay valid, `None` if they never expire.
+    pub token_ttl: Option<Duration>,
+    /// `User` granted [`HoneyUserRole::AppAdmin`] when first created, see
+    /// [`HoneyIdConfig::admin_pub_id`].
+    ///
+    /// [`HoneyUserRole::AppAdmin`]: crate::enums::HoneyUserRole::AppAdmin
+    /// [`HoneyIdConfig::admin_pub_id`]: crate::HoneyIdConfig::admin_pub_id
+    pub admin_pub_id: Option<UserPublicId>,
+    /// Receiver of audit events of this handler.
+    pub audit_sink: Option<Arc<dyn AuthAuditSink>>,
 }
 
 #[async_trait(?Send)]
@@ -70,11 +108,11 @@ impl RequestHandler for MethodReceiveToken {
     type Error = HoneyReceiveTokenError;
 
     async fn handle(&self, _ctx: RequestContext, req: Self::Request) -> Response<Self::Request, Self::Error> {
-        let token = uuid::Uuid::parse_str(&req.token)
-            .map_err(|_| HandlerError::Public(HoneyReceiveTokenError::InvalidToken))?;
+        let token = req.token;
         let user_pub_id = UserPublicId::from(req.userPubId);
 
-        self.user_storage
+        let outcome = self
+            .user_storage
             .create_or_update_user(CreateUserInfo {
                 username: req.username,
                 user_pub_id: req.userPubId,
@@ -82,11 +120,23 @@ impl RequestHandler for MethodReceiveToken {
             })
             .await
             .map_err(HandlerError::internal)?;
+        audit(self.audit_sink.as_deref(), user_upserted(outcome, user_pub_id, None));
+        bootstrap_admin(&*self.user_storage, self.admin_pub_id, user_pub_id, outcome)
+            .await
+            .map_err(HandlerError::internal)?;
 
+        let expires_at = token_expiry(self.token_ttl);
         self.token_storage
-            .store_token(user_pub_id, token)
+            .store_token(user_pub_id, token, expires_at)
             .await
             .map_err(HandlerError::internal)?;
+        audit(
+            self.audit_sink.as_deref(),
+            AuthAuditEventKind::TokenIssued {
+                user_pub_id,
+                expires_at,
+            },
+        );
 
         Ok(HoneyReceiveTokenResponse {})
     }
@@ -94,6 +144,16 @@ impl RequestHandler for MethodReceiveToken {
 pub struct MethodReceiveUserInfo {
     pub token_storage: Arc<dyn TokenStorage + Sync + Send>,
     pub user_storage: Arc<dyn UserStorage + Send + Sync>,
+    /// How long received tokens stay valid, `None` if they never expire.
+    pub token_ttl: Option<Duration>,
+    /// `User` granted [`HoneyUserRole::AppAdmin`] when first created, see
+    /// [`HoneyIdConfig::admin_pub_id`].
+    ///
+    /// [`HoneyUserRole::AppAdmin`]: crate::enums::HoneyUserRole::AppAdmin
+    /// [`HoneyIdConfig::admin_pub_id`]: crate::HoneyIdConfig::admin_pub_id
+    pub admin_pub_id: Option<UserPublicId>,
+    /// Receiver of audit events of this handler.
+    pub audit_sink: Option<Arc<dyn AuthAuditSink>>,
 }
 
 #[async_trait(?Send)]
@@ -104,7 +164,8 @@ impl RequestHandler for MethodReceiveUserInfo {
     async fn handle(&self, _ctx: RequestContext, req: Self::Request) -> Response<Self::Request, Self::Error> {
         let user_pub_id = UserPublicId::from(req.userPubId);
 
-        self.user_storage
+        let outcome = self
+            .user_storage
             .create_or_update_user(CreateUserInfo {
                 username: req.username,
                 user_pub_id: req.userPubId,
@@ -112,16 +173,27 @@ impl RequestHandler for MethodReceiveUserInfo {
             })
             .await
             .map_err(HandlerError::internal)?;
+        audit(
+            self.audit_sink.as_deref(),
+            user_upserted(outcome, user_pub_id, req.appPubId.map(AppPublicId::from)),
+        );
+        bootstrap_admin(&*self.user_storage, self.admin_pub_id, user_pub_id, outcome)
+            .await
+            .map_err(HandlerError::internal)?;
 
         if let Some(token) = req.token {
+            let expires_at = token_expiry(self.token_ttl);
             self.token_storage
-                .store_token(
-                    user_pub_id,
-                    Uuid::try_parse(&token)
-                        .map_err(|_| HandlerError::Public(HoneyReceiveUserInfoError::InvalidToken))?,
-                )
+                .store_token(user_pub_id, token, expires_at)
                 .await
                 .map_err(HandlerError::internal)?;
+            audit(
+                self.audit_sink.as_deref(),
+                AuthAuditEventKind::TokenIssued {
+                    user_pub_id,
+                    expires_at,
+                },
+            );
         }
 
         Ok(HoneyReceiveUserInfoResponse {})
@@ -131,6 +203,10 @@ impl RequestHandler for MethodReceiveUserInfo {
 pub struct MethodReceiveUserDeleted {
     pub token_storage: Arc<dyn TokenStorage + Sync + Send>,
     pub user_storage: Arc<dyn UserStorage + Send + Sync>,
+    /// Registry whose connections of the deleted `User` are kicked.
+    pub session_registry: Option<Arc<SessionRegistry>>,
+    /// Receiver of audit events of this handler.
+    pub audit_sink: Option<Arc<dyn AuthAuditSink>>,
 }
 
 #[async_trait(?Send)]
@@ -145,6 +221,9 @@ impl RequestHandler for MethodReceiveUserDeleted {
             .remove_tokens_for_user(user_pub_id)
             .await
             .map_err(HandlerError::internal)?;
+        if let Some(session_registry) = &self.session_registry {
+            session_registry.kick_user(user_pub_id);
+        }
 
         self.user_storage
             .delete_user(DeleteUserInfo {
@@ -153,13 +232,71 @@ impl RequestHandler for MethodReceiveUserDeleted {
             })
             .await
             .map_err(HandlerError::internal)?;
+        audit(
+            self.audit_sink.as_deref(),
+            AuthAuditEventKind::UserDeleted {
+                user_pub_id,
+                app_pub_id: req.appPubId.map(AppPublicId::from),
+            },
+        );
 
         Ok(HoneyReceiveUserDeletedResponse {})
     }
 }
 
+pub struct MethodReceiveUserStatusChanged {
+    pub token_storage: Arc<dyn TokenStorage + Sync + Send>,
+    pub user_storage: Arc<dyn UserStorage + Send + Sync>,
+    /// Registry whose connections of a `User` that is no longer
+    /// [`Enabled`](AuthUserStatus::Enabled) are kicked.
+    pub session_registry: Option<Arc<SessionRegistry>>,
+    /// Receiver of audit events of this handler.
+    pub audit_sink: Option<Arc<dyn AuthAuditSink>>,
+}
+
+#[async_trait(?Send)]
+impl RequestHandler for MethodReceiveUserStatusChanged {
+    type Request = HoneyReceiveUserStatusChangedRequest;
+    type Error = CustomError;
+
+    async fn handle(&self, _ctx: RequestContext, req: Self::Request) -> Response<Self::Request, Self::Error> {
+        let user_pub_id = UserPublicId::from(req.userPubId);
+
+        // Stored first, so a failure doesn't leave the tokens revoked under
+        // the old status when `honey.id` retries.
+        self.user_storage
+            .set_user_status(user_pub_id, req.status)
+            .await
+            .map_err(HandlerError::internal)?;
+        if req.status == AuthUserStatus::Banned {
+            self.token_storage
+                .remove_tokens_for_user(user_pub_id)
+                .await
+                .map_err(HandlerError::internal)?;
+        }
+        if req.status != AuthUserStatus::Enabled
+            && let Some(session_registry) = &self.session_registry
+        {
+            session_registry.kick_user(user_pub_id);
+        }
+        tracing::info!(%user_pub_id, status = %req.status, "Updated user status");
+        audit(
+            self.audit_sink.as_deref(),
+            AuthAuditEventKind::UserStatusChanged {
+                user_pub_id,
+                app_pub_id: req.appPubId.map(AppPublicId::from),
+                status: req.status,
+            },
+        );
+
+        Ok(HoneyReceiveUserStatusChangedResponse {})
+    }
+}
+
 pub struct MethodValidateToken {
     pub token_storage: Arc<dyn TokenStorage + Sync + Send>,
+    /// Receiver of audit events of this handler.
+    pub audit_sink: Option<Arc<dyn AuthAuditSink>>,
 }
 
 #[async_trait(?Send)]
@@ -168,18 +305,223 @@ impl RequestHandler for MethodValidateToken {
     type Error = HoneyValidateTokenError;
 
     async fn handle(&self, _ctx: RequestContext, req: Self::Request) -> Response<Self::Request, Self::Error> {
-        let token =
-            Uuid::parse_str(&req.token).map_err(|_| HandlerError::Public(HoneyValidateTokenError::InvalidToken))?;
-
-        match self.token_storage.validate_token(token).await {
-            Ok(user_pub_id) => Ok(HoneyValidateTokenResponse {
-                valid: true,
-                userPubId: Some(user_pub_id.into()),
-            }),
-            Err(_) => Ok(HoneyValidateTokenResponse {
-                valid: false,
-                userPubId: None,
-            }),
-        }
+        let user_pub_id = self.token_storage.validate_token(req.token).await.ok();
+        audit(
+            self.audit_sink.as_deref(),
+            AuthAuditEventKind::TokenValidated {
+                user_pub_id,
+                valid: user_pub_id.is_some(),
+            },
+        );
+
+        Ok(HoneyValidateTokenResponse {
+            valid: user_pub_id.is_some(),
+            userPubId: user_pub_id.map(Into::into),
+        })
+    }
+}
+
+/// Expiry timestamp (milliseconds) of a token received now, given its `ttl`.
+fn token_expiry(ttl: Option<Duration>) -> Option<i64> {
+    ttl.map(|ttl| get_time_milliseconds().saturating_add(i64::try_from(ttl.as_millis()).unwrap_or(i64::MAX)))
+}
+
+/// Audit event of a `User` upserted with provided `outcome`.
+fn user_upserted(
+    outcome: UserUpsertOutcome,
+    user_pub_id: UserPublicId,
+    app_pub_id: Option<AppPublicId>,
+) -> AuthAuditEventKind {
+    match outcome {
+        UserUpsertOutcome::Created => AuthAuditEventKind::UserCreated {
+            user_pub_id,
+            app_pub_id,
+        },
+        UserUpsertOutcome::Updated => AuthAuditEventKind::UserUpdated {
+            user_pub_id,
+            app_pub_id,
+        },
+    }
+}
+
+/// Grants the admin role to `user_pub_id` if it is the configured
+/// `admin_pub_id` and was just created.
+async fn bootstrap_admin(
+    user_storage: &(dyn UserStorage + Send + Sync),
+    admin_pub_id: Option<UserPublicId>,
+    user_pub_id: UserPublicId,
+    outcome: UserUpsertOutcome,
+) -> eyre::Result<()> {
+    if outcome != UserUpsertOutcome::Created || admin_pub_id != Some(user_pub_id) {
+        return Ok(());
+    }
+
+    user_storage.assign_admin_role(user_pub_id).await?;
+    tracing::info!(%user_pub_id, "Assigned admin role to configured admin user");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HoneyIdConfig;
+    use crate::credentials::{AccessToken, AppApiKey};
+    use crate::enums::{HoneyErrorCode, HoneyUserRole};
+    use crate::handlers::convenience_utils::audit::MemoryAuditSink;
+    use crate::handlers::convenience_utils::token_management::TokenWorkTableStorage;
+    use crate::handlers::convenience_utils::user_management::UserWorkTableStorage;
+    use crate::testing::FakeConnection;
+    use endpoint_libs::libs::peer::PeerIdentity;
+    use psc_nanoid::Nanoid;
+    use std::net::SocketAddr;
+
+    #[tokio::test]
+    async fn bootstraps_configured_admin_only_on_creation() {
+        let storage = UserWorkTableStorage::default();
+        let admin_pub_id = Nanoid::new();
+        let user_info = CreateUserInfo {
+            username: "admin".to_string(),
+            user_pub_id: admin_pub_id,
+            app_pub_id: None,
+        };
+        let admin_pub_id = UserPublicId::from(admin_pub_id);
+
+        let outcome = storage.create_or_update_user(user_info.clone()).await.unwrap();
+        bootstrap_admin(&storage, Some(admin_pub_id), admin_pub_id, outcome)
+            .await
+            .unwrap();
+        assert_eq!(storage.get_user(admin_pub_id).unwrap().role, HoneyUserRole::AppAdmin);
+
+        storage
+            .set_user_role(admin_pub_id, HoneyUserRole::AppSupport)
+            .await
+            .unwrap();
+        let outcome = storage.create_or_update_user(user_info).await.unwrap();
+        bootstrap_admin(&storage, Some(admin_pub_id), admin_pub_id, outcome)
+            .await
+            .unwrap();
+        assert_eq!(storage.get_user(admin_pub_id).unwrap().role, HoneyUserRole::AppSupport);
+    }
+
+    #[tokio::test]
+    async fn bans_users_missing_from_user_storage() {
+        let token_storage = Arc::new(TokenWorkTableStorage::default());
+        let handler = MethodReceiveUserStatusChanged {
+            token_storage: token_storage.clone(),
+            user_storage: Arc::new(UserWorkTableStorage::default()),
+            session_registry: None,
+            audit_sink: None,
+        };
+        let user_pub_id = Nanoid::new();
+        let token = AccessToken::generate();
+        token_storage
+            .store_token(user_pub_id.into(), token, None)
+            .await
+            .unwrap();
+
+        handler
+            .handle(
+                RequestContext::empty(),
+                HoneyReceiveUserStatusChangedRequest {
+                    userPubId: user_pub_id,
+                    appPubId: None,
+                    status: AuthUserStatus::Banned,
+                },
+            )
+            .await
+            .unwrap();
+
+        assert!(token_storage.validate_token(token).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn audits_received_and_validated_tokens() {
+        let token_storage = Arc::new(TokenWorkTableStorage::default());
+        let audit_sink = Arc::new(MemoryAuditSink::default());
+        let receive_token = MethodReceiveToken {
+            token_storage: token_storage.clone(),
+            user_storage: Arc::new(UserWorkTableStorage::default()),
+            token_ttl: None,
+            admin_pub_id: None,
+            audit_sink: Some(audit_sink.clone()),
+        };
+        let validate_token = MethodValidateToken {
+            token_storage,
+            audit_sink: Some(audit_sink.clone()),
+        };
+        let user_pub_id = Nanoid::new();
+        let token = AccessToken::generate();
+
+        receive_token
+            .handle(
+                RequestContext::empty(),
+                HoneyReceiveTokenRequest {
+                    token,
+                    username: "alice".to_string(),
+                    userPubId: user_pub_id,
+                },
+            )
+            .await
+            .unwrap();
+        validate_token
+            .handle(RequestContext::empty(), HoneyValidateTokenRequest { token })
+            .await
+            .unwrap();
+
+        let user_pub_id = UserPublicId::from(user_pub_id);
+        let events: Vec<_> = audit_sink.take().into_iter().map(|event| event.kind).collect();
+        assert_eq!(
+            events,
+            [
+                AuthAuditEventKind::UserCreated {
+                    user_pub_id,
+                    app_pub_id: None,
+                },
+                AuthAuditEventKind::TokenIssued {
+                    user_pub_id,
+                    expires_at: None,
+                },
+                AuthAuditEventKind::TokenValidated {
+                    user_pub_id: Some(user_pub_id),
+                    valid: true,
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn authorizes_only_configured_api_key() {
+        let user_storage = Arc::new(UserWorkTableStorage::default());
+        let audit_sink = Arc::new(MemoryAuditSink::default());
+        let handler = Arc::new(MethodApiKeyConnect {
+            honey_id_client: Arc::new(HoneyIdClient::new(HoneyIdConfig {
+                auth_api_key: AppApiKey::from("auth-key"),
+                ..HoneyIdConfig::default()
+            })),
+            user_storage: user_storage.clone(),
+            rate_limiter: None,
+            audit_sink: Some(audit_sink.clone()),
+        });
+        let request = |key: &str| HoneyApiKeyConnectRequest { appApiKey: key.into() };
+
+        let peer_addr = SocketAddr::from(([10, 0, 0, 1], 4242));
+        let fake = FakeConnection::new(PeerIdentity::Network(peer_addr));
+        let failure = fake.auth(handler.clone(), request("wrong-key")).await.unwrap_err();
+        assert_eq!(failure.code(), Some(HoneyErrorCode::Unauthorized.into()));
+        assert!(fake.roles().is_empty());
+        assert_eq!(
+            audit_sink
+                .take()
+                .into_iter()
+                .map(|event| event.kind)
+                .collect::<Vec<_>>(),
+            [AuthAuditEventKind::ApiKeyFailure {
+                peer_addr: peer_addr.ip()
+            }]
+        );
+
+        fake.auth(handler, request("auth-key")).await.unwrap();
+        assert_eq!(fake.roles(), [user_storage.get_honey_auth_role()]);
     }
 }
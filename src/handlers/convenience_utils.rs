@@ -1,4 +1,7 @@
 //! Defines some convenience utilities to prevent duplicated code on every app backend that uses the honey client
+pub mod audit;
 pub mod generic_auth_handler;
+pub mod rate_limiting;
+pub mod session_management;
 pub mod token_management;
 pub mod user_management;
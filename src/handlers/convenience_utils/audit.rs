@@ -0,0 +1,183 @@
+//! Definition of [`AuthAuditSink`] and its implementations.
+//!
+//! The Auth to App handlers report every security relevant state change as an
+//! [`AuthAuditEvent`] to an optional [`AuthAuditSink`], e.g. for compliance
+//! reporting.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Mutex;
+
+use endpoint_libs::libs::utils::get_time_milliseconds;
+use serde::Serialize;
+
+use crate::enums::AuthUserStatus;
+use crate::id_entities::{AppPublicId, UserPublicId};
+
+/// Audit record of a security relevant state change.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AuthAuditEvent {
+    /// Unix timestamp (milliseconds) at which the event happened.
+    pub timestamp: i64,
+    #[serde(flatten)]
+    pub kind: AuthAuditEventKind,
+}
+
+impl AuthAuditEvent {
+    /// Creates new [`AuthAuditEvent`] of provided `kind` happening now.
+    pub fn now(kind: AuthAuditEventKind) -> Self {
+        Self {
+            timestamp: get_time_milliseconds(),
+            kind,
+        }
+    }
+}
+
+/// Kind of an [`AuthAuditEvent`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AuthAuditEventKind {
+    /// Token of a `User` was stored. `expires_at` is a Unix timestamp
+    /// (milliseconds).
+    TokenIssued {
+        user_pub_id: UserPublicId,
+        expires_at: Option<i64>,
+    },
+    /// Token was validated on behalf of `honey.id`.
+    TokenValidated {
+        user_pub_id: Option<UserPublicId>,
+        valid: bool,
+    },
+    UserCreated {
+        user_pub_id: UserPublicId,
+        app_pub_id: Option<AppPublicId>,
+    },
+    UserUpdated {
+        user_pub_id: UserPublicId,
+        app_pub_id: Option<AppPublicId>,
+    },
+    UserDeleted {
+        user_pub_id: UserPublicId,
+        app_pub_id: Option<AppPublicId>,
+    },
+    UserStatusChanged {
+        user_pub_id: UserPublicId,
+        app_pub_id: Option<AppPublicId>,
+        status: AuthUserStatus,
+    },
+    /// Connection presented a wrong `Auth` API key.
+    ApiKeyFailure { peer_addr: IpAddr },
+}
+
+/// Receiver of [`AuthAuditEvent`]s.
+///
+/// Recording must not fail the reported operation, so sinks handle their own
+/// errors.
+pub trait AuthAuditSink: Send + Sync {
+    fn record(&self, event: AuthAuditEvent);
+}
+
+/// [`AuthAuditSink`] appending events as JSON lines to a file.
+#[derive(Debug)]
+pub struct JsonLinesAuditSink {
+    file: Mutex<File>,
+}
+
+impl JsonLinesAuditSink {
+    /// Opens the file at `path` for appending, creating it if missing.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(Self { file: Mutex::new(file) })
+    }
+}
+
+impl AuthAuditSink for JsonLinesAuditSink {
+    fn record(&self, event: AuthAuditEvent) {
+        let mut line = match serde_json::to_vec(&event) {
+            Ok(line) => line,
+            Err(err) => {
+                tracing::error!(%err, ?event, "Failed to serialize audit event");
+                return;
+            }
+        };
+        line.push(b'\n');
+
+        if let Err(err) = self.file.lock().unwrap().write_all(&line) {
+            tracing::error!(%err, ?event, "Failed to write audit event");
+        }
+    }
+}
+
+/// [`AuthAuditSink`] keeping events in memory, e.g. for tests.
+#[derive(Debug, Default)]
+pub struct MemoryAuditSink {
+    events: Mutex<Vec<AuthAuditEvent>>,
+}
+
+impl MemoryAuditSink {
+    /// Returns all recorded events, oldest first.
+    pub fn events(&self) -> Vec<AuthAuditEvent> {
+        self.events.lock().unwrap().clone()
+    }
+
+    /// Removes and returns all recorded events, oldest first.
+    pub fn take(&self) -> Vec<AuthAuditEvent> {
+        std::mem::take(&mut *self.events.lock().unwrap())
+    }
+}
+
+impl AuthAuditSink for MemoryAuditSink {
+    fn record(&self, event: AuthAuditEvent) {
+        self.events.lock().unwrap().push(event);
+    }
+}
+
+/// Records an event of provided `kind` to `sink`, if any.
+pub(crate) fn audit(sink: Option<&dyn AuthAuditSink>, kind: AuthAuditEventKind) {
+    if let Some(sink) = sink {
+        sink.record(AuthAuditEvent::now(kind));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use psc_nanoid::Nanoid;
+    use uuid::Uuid;
+
+    #[test]
+    fn writes_events_as_json_lines() {
+        let path = std::env::temp_dir().join(format!("honey_id-audit-{}.jsonl", Uuid::new_v4()));
+        let sink = JsonLinesAuditSink::open(&path).unwrap();
+        let user_pub_id = UserPublicId::from(Nanoid::new());
+
+        let events = [
+            AuthAuditEvent::now(AuthAuditEventKind::UserCreated {
+                user_pub_id,
+                app_pub_id: None,
+            }),
+            AuthAuditEvent::now(AuthAuditEventKind::TokenIssued {
+                user_pub_id,
+                expires_at: Some(42),
+            }),
+        ];
+        events.iter().cloned().for_each(|event| sink.record(event));
+
+        let lines: Vec<serde_json::Value> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["event"], "user_created");
+        assert_eq!(lines[0]["user_pub_id"], user_pub_id.to_string());
+        assert_eq!(lines[1]["event"], "token_issued");
+        assert_eq!(lines[1]["expires_at"], 42);
+        assert_eq!(lines[1]["timestamp"], events[1].timestamp);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -1,7 +1,7 @@
 //! Generic authentication handler that allows apps to define custom connection logic
 //! without duplicating the common auth infrastructure.
 
-use crate::enums::HoneyErrorCode;
+use crate::enums::{AuthUserStatus, HoneyErrorCode};
 use std::future::Future;
 use std::marker::PhantomData;
 use std::sync::Arc;
@@ -15,17 +15,19 @@ use futures::FutureExt;
 use futures::future::LocalBoxFuture;
 use serde::de::DeserializeOwned;
 use tracing;
-use uuid::Uuid;
 
+use super::rate_limiting::{AuthRateLimiter, RateLimitKey, check_attempt, record_failed_attempt};
+use super::session_management::SessionRegistry;
 use super::token_management::TokenStorage;
-use super::user_management::UserStorage;
+use super::user_management::{UserRoleSet, UserStorage};
+use crate::credentials::{AccessToken, InvalidAccessToken};
 use crate::id_entities::UserPublicId;
 
 /// Context passed to the `on_connect` callback for authorized connections.
 /// Extensible: add fields here to give apps more info without changing their closure signature.
 pub struct AuthorizedConnectContext {
     pub user_pub_id: UserPublicId,
-    pub user_api_roles: Vec<u32>,
+    pub user_api_roles: UserRoleSet,
     pub conn: Arc<WsConnection>,
 }
 
@@ -35,10 +37,17 @@ pub struct PublicConnectContext {
 }
 
 /// Trait app request types must implement to work with [`GenericAuthorizedConnect`].
-/// The handler calls `get_access_token()` to extract the token for validation
+/// The handler calls `access_token()` to extract the token for validation
 /// without knowing the concrete request type.
 pub trait AuthorizedConnectRequest: DeserializeOwned + Send + Sync + 'static {
+    /// Returns the access token as sent by the client.
     fn get_access_token(&self) -> &str;
+
+    /// Parses the [`AccessToken`] returned by
+    /// [`get_access_token`](Self::get_access_token).
+    fn access_token(&self) -> Result<AccessToken, InvalidAccessToken> {
+        self.get_access_token().parse()
+    }
 }
 
 /// Marker trait app request types must implement to work with [`GenericPublicConnect`].
@@ -115,7 +124,8 @@ where
 
 /// Generic handler for authorized WebSocket connections with custom request/response types.
 ///
-/// This handler validates access tokens, assigns roles, and delegates to a custom callback.
+/// This handler validates access tokens, refuses `User`'s that are not
+/// [`Enabled`](AuthUserStatus::Enabled), assigns roles, and delegates to a custom callback.
 ///
 /// # Example
 ///
@@ -147,6 +157,12 @@ where
 {
     pub token_storage: Arc<dyn TokenStorage + Sync + Send>,
     pub user_storage: Arc<dyn UserStorage + Sync + Send>,
+    /// Registry recording authorized connections, see
+    /// [`with_session_registry`](Self::with_session_registry).
+    pub session_registry: Option<Arc<SessionRegistry>>,
+    /// Limiter of failed attempts, see
+    /// [`with_rate_limiter`](Self::with_rate_limiter).
+    pub rate_limiter: Option<Arc<dyn AuthRateLimiter>>,
     on_connect: Arc<dyn Fn(Req, AuthorizedConnectContext) -> LocalBoxFuture<'static, Result<Res>> + Send + Sync>,
     _phantom: PhantomData<(Req, Res)>,
 }
@@ -175,10 +191,33 @@ where
         Self {
             token_storage,
             user_storage,
+            session_registry: None,
+            rate_limiter: None,
             on_connect: on_connect_boxed,
             _phantom: PhantomData,
         }
     }
+
+    /// Records every authorized connection in `session_registry`, so that
+    /// it can be kicked once its token is revoked or its `User` is deleted or
+    /// banned.
+    ///
+    /// The `token_storage` needs the same registry to kick connections of
+    /// revoked, evicted or expired tokens.
+    #[must_use]
+    pub fn with_session_registry(mut self, session_registry: Arc<SessionRegistry>) -> Self {
+        self.session_registry = Some(session_registry);
+        self
+    }
+
+    /// Throttles failed attempts with `rate_limiter`, keyed by peer address
+    /// and access token prefix. Throttled attempts are answered with
+    /// [`HoneyErrorCode::TooManyRequests`].
+    #[must_use]
+    pub fn with_rate_limiter(mut self, rate_limiter: Arc<dyn AuthRateLimiter>) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
 }
 
 #[async_trait(?Send)]
@@ -192,16 +231,24 @@ where
 
     fn auth(
         self: Arc<Self>,
-        _toolbox: &ArcToolbox,
+        toolbox: &ArcToolbox,
         req: Self::Request,
         _ctx: RequestContext,
         conn: Arc<WsConnection>,
     ) -> LocalBoxFuture<'static, Response<Self::Request, Self::Error>> {
+        let toolbox = toolbox.clone();
         async move {
-            let token = Uuid::parse_str(req.get_access_token())
-                .map_err(|_| CustomError::new(HoneyErrorCode::Unauthorized).with_message("Wrong accessToken"))?;
+            let rate_limiter = self.rate_limiter.as_deref();
+            let attempt_keys = RateLimitKey::for_attempt(&conn.peer, req.get_access_token());
+            check_attempt(rate_limiter, &attempt_keys)?;
+
+            let token = req.access_token().map_err(|_| {
+                record_failed_attempt(rate_limiter, &attempt_keys);
+                CustomError::new(HoneyErrorCode::Unauthorized).with_message("Wrong accessToken")
+            })?;
 
             let Ok(user_pub_id) = self.token_storage.validate_token(token).await else {
+                record_failed_attempt(rate_limiter, &attempt_keys);
                 tracing::error!(
                     error = "Wrong `accessToken`",
                     "`GenericAuthorizedConnect` failed to validate the `accessToken`."
@@ -211,11 +258,31 @@ where
                     .into());
             };
 
+            let status = self
+                .user_storage
+                .get_user_status(user_pub_id)
+                .await
+                .map_err(HandlerError::internal)?;
+            if status != AuthUserStatus::Enabled {
+                tracing::warn!(
+                    %user_pub_id,
+                    %status,
+                    "`GenericAuthorizedConnect` refused connection of a user that is not enabled."
+                );
+                return Err(CustomError::new(HoneyErrorCode::Forbidden)
+                    .with_message(format!("User is {status}"))
+                    .into());
+            }
+
             let roles = self
                 .user_storage
                 .get_api_roles_by_pub_id(user_pub_id)
+                .await
                 .map_err(HandlerError::internal)?;
-            conn.set_roles(Arc::new(roles.clone()));
+            conn.set_roles(Arc::new(roles.to_vec()));
+            if let Some(session_registry) = &self.session_registry {
+                session_registry.register(user_pub_id, token, &conn, &toolbox);
+            }
 
             let ctx = AuthorizedConnectContext {
                 user_pub_id,
@@ -343,13 +410,22 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::endpoints::connect::{
+        HoneyAuthorizedConnectRequest, HoneyAuthorizedConnectResponse, HoneyPublicConnectRequest,
+        HoneyPublicConnectResponse,
+    };
+    use crate::handlers::convenience_utils::session_management::SessionRegistry;
+    use crate::handlers::convenience_utils::token_management::{PersistedTokenWorkTableStorage, TokenWorkTableStorage};
+    use crate::handlers::convenience_utils::user_management::{CreateUserInfo, UserRolePolicy, UserWorkTableStorage};
+    use crate::handlers::user_to_app::{MethodAuthorizedConnect, MethodPublicConnect};
+    use crate::testing::FakeConnection;
+    use endpoint_libs::libs::utils::get_time_milliseconds;
+    use psc_nanoid::Nanoid;
 
     #[test]
     fn closure_handler_is_send_sync() {
         fn assert_send_sync<T: Send + Sync>() {}
 
-        use crate::endpoints::connect::{HoneyPublicConnectRequest, HoneyPublicConnectResponse};
-
         assert_send_sync::<
             ClosureHandler<
                 fn(
@@ -359,4 +435,167 @@ mod tests {
             >,
         >();
     }
+
+    #[tokio::test]
+    async fn authorizes_enabled_users_with_valid_token() {
+        let token_storage = Arc::new(TokenWorkTableStorage::default());
+        let user_storage = Arc::new(UserWorkTableStorage::default());
+        let user_pub_id = Nanoid::new();
+        user_storage
+            .create_or_update_user(CreateUserInfo {
+                username: "alice".to_string(),
+                user_pub_id,
+                app_pub_id: None,
+            })
+            .await
+            .unwrap();
+        let user_pub_id = UserPublicId::from(user_pub_id);
+        let token = AccessToken::generate();
+        token_storage.store_token(user_pub_id, token, None).await.unwrap();
+        let handler = Arc::new(MethodAuthorizedConnect::new(
+            token_storage,
+            user_storage.clone(),
+            |_req, _ctx| async { Ok(HoneyAuthorizedConnectResponse {}) },
+        ));
+        let request = |access_token: String| HoneyAuthorizedConnectRequest {
+            accessToken: access_token.into(),
+        };
+
+        let fake = FakeConnection::default();
+        let failure = fake
+            .auth(
+                handler.clone(),
+                request(AccessToken::generate().expose_secret().to_string()),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(failure.code(), Some(HoneyErrorCode::Unauthorized.into()));
+        assert!(fake.roles().is_empty());
+
+        fake.auth(handler.clone(), request(token.expose_secret().to_string()))
+            .await
+            .unwrap();
+        let roles = user_storage.get_api_roles_by_pub_id(user_pub_id).await.unwrap();
+        assert_eq!(fake.roles(), roles.to_vec());
+
+        user_storage
+            .set_user_status(user_pub_id, AuthUserStatus::Banned)
+            .await
+            .unwrap();
+        let fake = FakeConnection::default();
+        let failure = fake
+            .auth(handler, request(token.expose_secret().to_string()))
+            .await
+            .unwrap_err();
+        assert_eq!(failure.code(), Some(HoneyErrorCode::Forbidden.into()));
+        assert_eq!(failure.message(), Some("User is Banned"));
+        assert!(fake.roles().is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn authorizes_persisted_tokens_after_restart() {
+        let data_dir = std::env::temp_dir().join(format!("honey_id-restart-{}", uuid::Uuid::new_v4()));
+        let data_dir = data_dir.to_string_lossy().into_owned();
+        let user_pub_id = Nanoid::new();
+        let token = AccessToken::generate();
+        {
+            let token_storage = PersistedTokenWorkTableStorage::open(data_dir.clone()).await.unwrap();
+            let user_storage = UserWorkTableStorage::default();
+            user_storage
+                .create_or_update_user(CreateUserInfo {
+                    username: "alice".to_string(),
+                    user_pub_id,
+                    app_pub_id: None,
+                })
+                .await
+                .unwrap();
+            token_storage
+                .store_token(user_pub_id.into(), token, None)
+                .await
+                .unwrap();
+            token_storage.flush().await;
+        }
+
+        // Users are only re-created by the next `honey.id` callback.
+        let token_storage = Arc::new(PersistedTokenWorkTableStorage::open(data_dir.clone()).await.unwrap());
+        let user_storage = Arc::new(UserWorkTableStorage::default());
+        let handler = Arc::new(MethodAuthorizedConnect::new(
+            token_storage,
+            user_storage,
+            |_req, _ctx| async { Ok(HoneyAuthorizedConnectResponse {}) },
+        ));
+
+        let fake = FakeConnection::default();
+        let request = HoneyAuthorizedConnectRequest {
+            accessToken: token.expose_secret().to_string().into(),
+        };
+        fake.auth(handler, request).await.unwrap();
+        assert_eq!(fake.roles(), vec![UserRolePolicy::default().default_role as u32]);
+
+        std::fs::remove_dir_all(data_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn kicks_connections_of_revoked_evicted_and_expired_tokens() {
+        let session_registry = Arc::new(SessionRegistry::default());
+        let token_storage = Arc::new(
+            TokenWorkTableStorage::default()
+                .with_max_sessions_per_user(1)
+                .with_session_registry(session_registry.clone()),
+        );
+        let user_storage = Arc::new(UserWorkTableStorage::default());
+        let user_pub_id = Nanoid::new();
+        user_storage
+            .create_or_update_user(CreateUserInfo {
+                username: "alice".to_string(),
+                user_pub_id,
+                app_pub_id: None,
+            })
+            .await
+            .unwrap();
+        let user_pub_id = UserPublicId::from(user_pub_id);
+        let handler = Arc::new(
+            MethodAuthorizedConnect::new(token_storage.clone(), user_storage, |_req, _ctx| async {
+                Ok(HoneyAuthorizedConnectResponse {})
+            })
+            .with_session_registry(session_registry),
+        );
+        let connect = async |token: AccessToken, expires_at: Option<i64>| {
+            token_storage.store_token(user_pub_id, token, expires_at).await.unwrap();
+            let fake = FakeConnection::default();
+            let request = HoneyAuthorizedConnectRequest {
+                accessToken: token.expose_secret().to_string().into(),
+            };
+            fake.auth(handler.clone(), request).await.unwrap();
+            assert!(!fake.roles().is_empty());
+            fake
+        };
+
+        let revoked = AccessToken::generate();
+        let fake = connect(revoked, None).await;
+        token_storage.revoke_token(revoked).await.unwrap();
+        assert!(fake.roles().is_empty());
+
+        let fake = connect(AccessToken::generate(), None).await;
+        let expiring = AccessToken::generate();
+        let expiring_fake = connect(expiring, Some(get_time_milliseconds() + 50)).await;
+        assert!(fake.roles().is_empty());
+
+        tokio::time::sleep(std::time::Duration::from_millis(60)).await;
+        assert_eq!(token_storage.remove_expired_tokens().await.unwrap(), 1);
+        assert!(expiring_fake.roles().is_empty());
+    }
+
+    #[tokio::test]
+    async fn assigns_public_roles() {
+        let user_storage = Arc::new(UserWorkTableStorage::default());
+        let handler = Arc::new(MethodPublicConnect::new(user_storage.clone(), |_req, _ctx| async {
+            Ok(HoneyPublicConnectResponse {})
+        }));
+
+        let fake = FakeConnection::default();
+        fake.auth(handler, HoneyPublicConnectRequest {}).await.unwrap();
+
+        assert_eq!(fake.roles(), user_storage.get_public_roles());
+    }
 }
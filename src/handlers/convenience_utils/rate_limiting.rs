@@ -0,0 +1,344 @@
+//! Definition of [`AuthRateLimiter`] and [`TokenBucketRateLimiter`].
+//!
+//! Throttles failed authentication attempts on
+//! [`GenericAuthorizedConnect`](super::generic_auth_handler::GenericAuthorizedConnect)
+//! and [`MethodApiKeyConnect`](crate::handlers::auth_to_app::MethodApiKeyConnect),
+//! so that access tokens and API keys can't be brute-forced.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::{IpAddr, Ipv6Addr};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use endpoint_libs::libs::peer::PeerIdentity;
+use endpoint_libs::libs::toolbox::CustomError;
+
+use crate::enums::HoneyErrorCode;
+
+/// Number of leading characters of a secret used as its [`RateLimitKey`].
+const TOKEN_PREFIX_LEN: usize = 8;
+
+/// Default maximum number of keys tracked by [`TokenBucketRateLimiter`].
+const DEFAULT_MAX_KEYS: usize = 65_536;
+
+/// Minimum time between two passes of [`TokenBucketRateLimiter`] dropping the
+/// buckets that refilled completely.
+const MIN_PRUNE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Key under which failed authentication attempts are counted.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub enum RateLimitKey {
+    /// IP address of the peer. IPv6 addresses are truncated to their `/64`
+    /// network, which a single client usually controls entirely. All local
+    /// peers share the loopback address.
+    Peer(IpAddr),
+    /// Leading characters of the presented access token or API key, so that
+    /// guesses of the same secret are throttled across peers.
+    TokenPrefix(String),
+}
+
+impl RateLimitKey {
+    /// Returns the keys of an authentication attempt by `peer` presenting
+    /// `secret`, under all of which failures are counted.
+    pub fn for_attempt(peer: &PeerIdentity, secret: &str) -> [Self; 2] {
+        let ip_addr = match peer.ip_addr() {
+            IpAddr::V6(ip_addr) => {
+                let network = u128::from(ip_addr) & !(u128::from(u64::MAX));
+                IpAddr::V6(Ipv6Addr::from(network))
+            }
+            ip_addr => ip_addr,
+        };
+
+        [
+            Self::Peer(ip_addr),
+            Self::TokenPrefix(secret.chars().take(TOKEN_PREFIX_LEN).collect()),
+        ]
+    }
+}
+
+impl std::fmt::Debug for RateLimitKey {
+    /// Keeps the token prefix out of logs.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Peer(ip_addr) => f.debug_tuple("Peer").field(ip_addr).finish(),
+            Self::TokenPrefix(_) => f.write_str("TokenPrefix(..)"),
+        }
+    }
+}
+
+/// Limiter of failed authentication attempts.
+pub trait AuthRateLimiter: Send + Sync {
+    /// Returns `false` if attempts under `key` are currently throttled.
+    fn check(&self, key: &RateLimitKey) -> bool;
+
+    /// Counts a failed authentication attempt under `key`.
+    fn record_failure(&self, key: &RateLimitKey);
+}
+
+/// In-memory [`AuthRateLimiter`] keeping a token bucket per
+/// [`RateLimitKey`].
+///
+/// Every failed attempt takes a token from the bucket of its key, and one
+/// token is given back every `refill_interval`. Attempts are throttled while
+/// the bucket is empty, so at most `capacity` failures in a row are allowed
+/// before the key is locked out.
+///
+/// At most [`max_keys`](Self::with_max_keys) buckets are kept, evicting the
+/// oldest one to make room for a new key. Buckets that refilled completely
+/// are dropped at most once per `refill_interval` (and no more often than
+/// once a second), so a failure costs amortised constant time.
+#[derive(Debug)]
+pub struct TokenBucketRateLimiter {
+    capacity: u32,
+    refill_interval: Duration,
+    max_keys: usize,
+    buckets: Mutex<Buckets>,
+}
+
+#[derive(Debug)]
+struct Buckets {
+    by_key: HashMap<RateLimitKey, Bucket>,
+    /// Keys in insertion order, with the `seq` of the bucket they were
+    /// inserted with. Entries of buckets that were dropped since are skipped.
+    order: VecDeque<(RateLimitKey, u64)>,
+    next_seq: u64,
+    pruned_at: Instant,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    seq: u64,
+    tokens: u32,
+    refilled_at: Instant,
+}
+
+impl Default for TokenBucketRateLimiter {
+    /// Allows 10 failures in a row, then one every 30 seconds.
+    fn default() -> Self {
+        Self::new(10, Duration::from_secs(30))
+    }
+}
+
+impl TokenBucketRateLimiter {
+    /// Creates new [`TokenBucketRateLimiter`] with buckets of `capacity`
+    /// tokens, refilled by one every `refill_interval`.
+    pub fn new(capacity: u32, refill_interval: Duration) -> Self {
+        Self {
+            capacity,
+            refill_interval: refill_interval.max(Duration::from_millis(1)),
+            max_keys: DEFAULT_MAX_KEYS,
+            buckets: Mutex::new(Buckets {
+                by_key: HashMap::new(),
+                order: VecDeque::new(),
+                next_seq: 0,
+                pruned_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Limits the number of keys tracked at once, 65536 by default.
+    #[must_use]
+    pub fn with_max_keys(mut self, max_keys: usize) -> Self {
+        self.max_keys = max_keys.max(1);
+        self
+    }
+
+    fn check_at(&self, key: &RateLimitKey, now: Instant) -> bool {
+        let mut buckets = self.buckets.lock().unwrap();
+        let Some(bucket) = buckets.by_key.get_mut(key) else {
+            return true;
+        };
+
+        self.refill(bucket, now);
+        bucket.tokens > 0
+    }
+
+    fn record_failure_at(&self, key: &RateLimitKey, now: Instant) {
+        let mut buckets = self.buckets.lock().unwrap();
+        self.prune(&mut buckets, now);
+
+        if !buckets.by_key.contains_key(key) {
+            self.evict(&mut buckets);
+            let seq = buckets.next_seq;
+            buckets.next_seq += 1;
+            buckets.order.push_back((key.clone(), seq));
+            buckets.by_key.insert(
+                key.clone(),
+                Bucket {
+                    seq,
+                    tokens: self.capacity,
+                    refilled_at: now,
+                },
+            );
+        }
+
+        let bucket = buckets.by_key.get_mut(key).expect("bucket was just inserted");
+        self.refill(bucket, now);
+        bucket.tokens = bucket.tokens.saturating_sub(1);
+    }
+
+    /// Drops the buckets that refilled completely, unless that was done less
+    /// than a `refill_interval` ago.
+    fn prune(&self, buckets: &mut Buckets, now: Instant) {
+        if now.saturating_duration_since(buckets.pruned_at) < self.refill_interval.max(MIN_PRUNE_INTERVAL) {
+            return;
+        }
+
+        let Buckets {
+            by_key,
+            order,
+            pruned_at,
+            ..
+        } = buckets;
+        by_key.retain(|_, bucket| {
+            self.refill(bucket, now);
+            bucket.tokens < self.capacity
+        });
+        order.retain(|(key, seq)| by_key.get(key).is_some_and(|bucket| bucket.seq == *seq));
+        *pruned_at = now;
+    }
+
+    /// Evicts the oldest buckets until there is room for a new key.
+    fn evict(&self, buckets: &mut Buckets) {
+        while buckets.by_key.len() >= self.max_keys {
+            let Some((key, seq)) = buckets.order.pop_front() else {
+                return;
+            };
+            if buckets.by_key.get(&key).is_some_and(|bucket| bucket.seq == seq) {
+                buckets.by_key.remove(&key);
+            }
+        }
+    }
+
+    fn refill(&self, bucket: &mut Bucket, now: Instant) {
+        let elapsed = now.saturating_duration_since(bucket.refilled_at);
+        let refills = elapsed.as_nanos() / self.refill_interval.as_nanos();
+        if refills == 0 {
+            return;
+        }
+
+        let missing = self.capacity - bucket.tokens;
+        if refills >= u128::from(missing) {
+            bucket.tokens = self.capacity;
+            bucket.refilled_at = now;
+        } else {
+            // `refills < missing <= u32::MAX`
+            let refills = refills as u32;
+            bucket.tokens += refills;
+            bucket.refilled_at += self.refill_interval * refills;
+        }
+    }
+}
+
+impl AuthRateLimiter for TokenBucketRateLimiter {
+    fn check(&self, key: &RateLimitKey) -> bool {
+        self.check_at(key, Instant::now())
+    }
+
+    fn record_failure(&self, key: &RateLimitKey) {
+        self.record_failure_at(key, Instant::now())
+    }
+}
+
+/// Returns the [`CustomError`] answered to a throttled attempt if any of
+/// `keys` is throttled by `rate_limiter`.
+pub(crate) fn check_attempt(
+    rate_limiter: Option<&dyn AuthRateLimiter>,
+    keys: &[RateLimitKey],
+) -> Result<(), CustomError> {
+    let Some(rate_limiter) = rate_limiter else {
+        return Ok(());
+    };
+
+    if let Some(key) = keys.iter().find(|key| !rate_limiter.check(key)) {
+        tracing::warn!(?key, "Throttled authentication attempt");
+        return Err(CustomError::new(HoneyErrorCode::TooManyRequests).with_message("Too many failed attempts"));
+    }
+
+    Ok(())
+}
+
+/// Counts a failed attempt under all `keys` of `rate_limiter`.
+pub(crate) fn record_failed_attempt(rate_limiter: Option<&dyn AuthRateLimiter>, keys: &[RateLimitKey]) {
+    if let Some(rate_limiter) = rate_limiter {
+        keys.iter().for_each(|key| rate_limiter.record_failure(key));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn throttles_key_until_bucket_refills() {
+        let limiter = TokenBucketRateLimiter::new(2, Duration::from_secs(10));
+        let key = RateLimitKey::TokenPrefix("deadbeef".to_string());
+        let other = RateLimitKey::Peer(IpAddr::from([127, 0, 0, 1]));
+        let start = Instant::now();
+
+        assert!(limiter.check_at(&key, start));
+        limiter.record_failure_at(&key, start);
+        assert!(limiter.check_at(&key, start));
+        limiter.record_failure_at(&key, start);
+        assert!(!limiter.check_at(&key, start));
+        assert!(limiter.check_at(&other, start));
+
+        assert!(!limiter.check_at(&key, start + Duration::from_secs(9)));
+        assert!(limiter.check_at(&key, start + Duration::from_secs(10)));
+        limiter.record_failure_at(&key, start + Duration::from_secs(10));
+        assert!(!limiter.check_at(&key, start + Duration::from_secs(19)));
+    }
+
+    #[test]
+    fn bounds_tracked_keys() {
+        let limiter = TokenBucketRateLimiter::new(1, Duration::from_secs(10)).with_max_keys(2);
+        let keys = [1, 2, 3].map(|byte| RateLimitKey::Peer(IpAddr::from([10, 0, 0, byte])));
+        let start = Instant::now();
+
+        for key in &keys {
+            limiter.record_failure_at(key, start);
+        }
+        assert!(limiter.check_at(&keys[0], start));
+        assert!(!limiter.check_at(&keys[1], start));
+        assert!(!limiter.check_at(&keys[2], start));
+
+        limiter.record_failure_at(&keys[0], start + Duration::from_secs(5));
+        limiter.record_failure_at(&keys[0], start + Duration::from_secs(10));
+        let buckets = limiter.buckets.lock().unwrap();
+        assert_eq!(buckets.by_key.len(), 1);
+        assert_eq!(buckets.order.len(), 1);
+    }
+
+    #[test]
+    fn keys_attempts_by_peer_network_and_token_prefix() {
+        let peer = |addr: &str| PeerIdentity::Network(format!("[{addr}]:4242").parse().unwrap());
+        let [peer_key, prefix_key] = RateLimitKey::for_attempt(&peer("2001:db8::1"), "deadbeef-secret");
+
+        assert_eq!(prefix_key, RateLimitKey::TokenPrefix("deadbeef".to_string()));
+        assert_eq!(format!("{prefix_key:?}"), "TokenPrefix(..)");
+        assert_eq!(
+            peer_key,
+            RateLimitKey::for_attempt(&peer("2001:db8::ffff:1"), "other")[0]
+        );
+        assert_ne!(
+            peer_key,
+            RateLimitKey::for_attempt(&peer("2001:db8:0:1::1"), "other")[0]
+        );
+    }
+
+    #[test]
+    fn throttles_attempt_when_any_key_is_throttled() {
+        let limiter = TokenBucketRateLimiter::new(1, Duration::from_secs(10));
+        let peer = |addr: &str| PeerIdentity::Network(addr.parse().unwrap());
+        let first = RateLimitKey::for_attempt(&peer("10.0.0.1:4242"), "deadbeef-first");
+        let same_secret = RateLimitKey::for_attempt(&peer("10.0.0.2:4242"), "deadbeef-second");
+        let same_peer = RateLimitKey::for_attempt(&peer("10.0.0.1:4343"), "cafebabe");
+        let unrelated = RateLimitKey::for_attempt(&peer("10.0.0.3:4242"), "cafebabe");
+
+        record_failed_attempt(Some(&limiter), &first);
+
+        assert!(check_attempt(Some(&limiter), &same_secret).is_err());
+        assert!(check_attempt(Some(&limiter), &same_peer).is_err());
+        assert!(check_attempt(Some(&limiter), &unrelated).is_ok());
+    }
+}
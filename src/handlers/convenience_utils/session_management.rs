@@ -0,0 +1,194 @@
+//! Definition of [`SessionRegistry`].
+//!
+//! Tracks the [`WsConnection`]s authorized by
+//! [`GenericAuthorizedConnect`](super::generic_auth_handler::GenericAuthorizedConnect),
+//! so that they can be kicked once their token is revoked, evicted or expired,
+//! or their `User` is deleted or banned.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, Weak};
+
+use endpoint_libs::libs::toolbox::ArcToolbox;
+use endpoint_libs::libs::ws::{WsConnection, WsResponseValue};
+
+use crate::credentials::AccessToken;
+use crate::id_entities::UserPublicId;
+
+/// Authorized connection recorded in [`SessionRegistry`].
+struct Session {
+    token: AccessToken,
+    conn: Weak<WsConnection>,
+    toolbox: ArcToolbox,
+}
+
+/// Registry of live authorized connections, keyed by [`UserPublicId`].
+///
+/// Connections are held weakly, so a connection that was closed by its peer
+/// is dropped from the registry on the next access for its `User`.
+///
+/// Kicking a connection doesn't close it, since `endpoint-libs` can't close a
+/// socket from outside of its session. Instead, the connection loses all its
+/// roles, so that every further request on it is refused, and is sent a
+/// [`WsResponseValue::Close`] message. That is a regular text message, not a
+/// WebSocket close frame: `endpoint-libs` clients close the socket on
+/// receiving it, while other clients keep the socket open, without roles,
+/// until they close it.
+///
+/// Pass the registry to the token storage too (e.g.
+/// [`TokenWorkTableStorage::with_session_registry`]), so that connections are
+/// also kicked once their token is revoked, evicted or expired.
+///
+/// [`TokenWorkTableStorage::with_session_registry`]: super::token_management::TokenWorkTableStorage::with_session_registry
+#[derive(Default)]
+pub struct SessionRegistry {
+    sessions: Mutex<HashMap<UserPublicId, Vec<Session>>>,
+}
+
+impl SessionRegistry {
+    /// Records `conn`, authorized as `User` with provided [`UserPublicId`]
+    /// using `token`.
+    pub fn register(
+        &self,
+        user_pub_id: UserPublicId,
+        token: AccessToken,
+        conn: &Arc<WsConnection>,
+        toolbox: &ArcToolbox,
+    ) {
+        let mut sessions = self.sessions.lock().unwrap();
+        let user_sessions = sessions.entry(user_pub_id).or_default();
+
+        user_sessions.retain(|session| session.conn.strong_count() > 0);
+        user_sessions.push(Session {
+            token,
+            conn: Arc::downgrade(conn),
+            toolbox: toolbox.clone(),
+        });
+    }
+
+    /// Returns live connections of `User` with provided [`UserPublicId`].
+    pub fn connections_for_user(&self, user_pub_id: UserPublicId) -> Vec<Arc<WsConnection>> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let Some(user_sessions) = sessions.get_mut(&user_pub_id) else {
+            return Vec::new();
+        };
+
+        user_sessions.retain(|session| session.conn.strong_count() > 0);
+        let conns = user_sessions
+            .iter()
+            .filter_map(|session| session.conn.upgrade())
+            .collect();
+        if user_sessions.is_empty() {
+            sessions.remove(&user_pub_id);
+        }
+
+        conns
+    }
+
+    /// Kicks all connections of `User` with provided [`UserPublicId`].
+    ///
+    /// Returns the number of kicked connections.
+    pub fn kick_user(&self, user_pub_id: UserPublicId) -> usize {
+        let kicked = self.sessions.lock().unwrap().remove(&user_pub_id).unwrap_or_default();
+
+        kick(user_pub_id, kicked)
+    }
+
+    /// Kicks all connections that were authorized using `token`.
+    ///
+    /// Returns the number of kicked connections.
+    pub fn kick_token(&self, token: AccessToken) -> usize {
+        self.kick_tokens([token])
+    }
+
+    /// Kicks all connections that were authorized using any of `tokens`.
+    ///
+    /// Returns the number of kicked connections.
+    pub fn kick_tokens(&self, tokens: impl IntoIterator<Item = AccessToken>) -> usize {
+        let tokens: HashSet<AccessToken> = tokens.into_iter().collect();
+        if tokens.is_empty() {
+            return 0;
+        }
+
+        let mut sessions = self.sessions.lock().unwrap();
+        let mut kicked_count = 0;
+
+        sessions.retain(|user_pub_id, user_sessions| {
+            let (kicked, kept) = std::mem::take(user_sessions)
+                .into_iter()
+                .partition(|session| tokens.contains(&session.token));
+            *user_sessions = kept;
+            kicked_count += kick(*user_pub_id, kicked);
+
+            !user_sessions.is_empty()
+        });
+
+        kicked_count
+    }
+}
+
+fn kick(user_pub_id: UserPublicId, sessions: Vec<Session>) -> usize {
+    let mut kicked = 0;
+    for session in sessions {
+        let Some(conn) = session.conn.upgrade() else {
+            continue;
+        };
+
+        conn.set_roles(Arc::new(Vec::new()));
+        session.toolbox.send(conn.connection_id, WsResponseValue::Close);
+        kicked += 1;
+    }
+
+    if kicked > 0 {
+        tracing::info!(%user_pub_id, kicked, "Kicked authorized connections");
+    }
+
+    kicked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use endpoint_libs::libs::peer::{Extensions, PeerIdentity};
+    use endpoint_libs::libs::toolbox::Toolbox;
+    use psc_nanoid::Nanoid;
+    use std::sync::atomic::AtomicU64;
+
+    fn conn(connection_id: u32) -> Arc<WsConnection> {
+        let conn = Arc::new(WsConnection {
+            connection_id,
+            user_id: AtomicU64::new(0),
+            roles: Default::default(),
+            peer: PeerIdentity::Unknown,
+            extensions: Extensions::new(),
+            log_id: 0,
+        });
+        conn.set_roles(Arc::new(vec![1]));
+        conn
+    }
+
+    #[test]
+    fn kicks_connections_by_user_and_by_token() {
+        let registry = SessionRegistry::default();
+        let toolbox = Toolbox::new();
+        let alice = UserPublicId::from(Nanoid::new());
+        let bob = UserPublicId::from(Nanoid::new());
+        let (alice_token, bob_token) = (AccessToken::generate(), AccessToken::generate());
+
+        let alice_conn = conn(1);
+        let bob_conns = [conn(2), conn(3)];
+        registry.register(alice, alice_token, &alice_conn, &toolbox);
+        for bob_conn in &bob_conns {
+            registry.register(bob, bob_token, bob_conn, &toolbox);
+        }
+        registry.register(bob, bob_token, &conn(4), &toolbox);
+        assert_eq!(registry.connections_for_user(bob).len(), 2);
+
+        assert_eq!(registry.kick_token(bob_token), 2);
+        assert!(bob_conns.iter().all(|conn| conn.get_roles().is_empty()));
+        assert_eq!(*alice_conn.get_roles(), vec![1]);
+
+        assert_eq!(registry.kick_user(alice), 1);
+        assert!(alice_conn.get_roles().is_empty());
+        assert!(registry.connections_for_user(alice).is_empty());
+    }
+}
@@ -1,17 +1,35 @@
-//! Definition of [`TokenWorkTableStorage`].
+//! Definition of [`TokenWorkTableStorage`] and
+//! [`PersistedTokenWorkTableStorage`].
 //!
 //! This module defines the in-memory [`TokenWorkTable`] for storing
 //! authentication tokens and their associated [`UserPublicId`] and
 //! [`TokenWorkTableStorage`] newtype.
 //!
-//! Tokens are not persisted, so `User`'s should re-login on app restart.
+//! Tokens of [`TokenWorkTableStorage`] are not persisted, so `User`'s should
+//! re-login on app restart. [`PersistedTokenWorkTableStorage`] stores the
+//! same data on disk with [`PersistedTokenWorkTable`], so tokens survive
+//! restarts.
+//!
+//! Expired tokens are rejected by [`TokenStorage::validate_token`] and can be
+//! removed with [`TokenWorkTableStorage::spawn_expiry_sweeper`].
+//!
+//! With a [`SessionRegistry`], connections authorized with a token are kicked
+//! once it is revoked, evicted or removed as expired.
+
+use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
-use eyre::eyre;
+use endpoint_libs::libs::utils::get_time_milliseconds;
+use eyre::{bail, eyre};
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
 use uuid::Uuid;
 use worktable::prelude::*;
 use worktable::worktable;
 
+use crate::credentials::AccessToken;
+use crate::handlers::convenience_utils::session_management::SessionRegistry;
 use crate::types::id_entities::UserPublicId;
 
 /// Describes the API of [`TokenStorage`], which simplifies and abstracts the storage and validation
@@ -20,12 +38,37 @@ use crate::types::id_entities::UserPublicId;
 pub trait TokenStorage {
     /// Stores received `token` which belongs to `User` with provided
     /// [`UserPublicId`].
-    async fn store_token(&self, user_pub_id: UserPublicId, token: Uuid) -> eyre::Result<()>;
+    ///
+    /// `expires_at` is the Unix timestamp (milliseconds) after which the
+    /// `token` is no longer valid, `None` if it never expires.
+    async fn store_token(
+        &self,
+        user_pub_id: UserPublicId,
+        token: AccessToken,
+        expires_at: Option<i64>,
+    ) -> eyre::Result<()>;
     /// Validates provided `token` and returns User internal ID: u64, and [`UserPublicId`] if `token` is
-    /// valid. Errors otherwise.
-    async fn validate_token(&self, token: Uuid) -> eyre::Result<UserPublicId>;
+    /// valid. Errors otherwise, including when `token` has expired.
+    async fn validate_token(&self, token: AccessToken) -> eyre::Result<UserPublicId>;
     /// Remove all tokens associated with a user.
     async fn remove_tokens_for_user(&self, user_pub_id: UserPublicId) -> eyre::Result<()>;
+    /// Removes a single `token`, e.g. when the `User` logs out on one device.
+    async fn revoke_token(&self, token: AccessToken) -> eyre::Result<()>;
+    /// Lists the tokens of `User` with provided [`UserPublicId`], oldest
+    /// first.
+    async fn list_tokens_for_user(&self, user_pub_id: UserPublicId) -> eyre::Result<Vec<TokenSession>>;
+}
+
+/// Token stored for a `User`, as returned by
+/// [`TokenStorage::list_tokens_for_user`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TokenSession {
+    pub token: AccessToken,
+    /// Unix timestamp (milliseconds) at which the token was stored.
+    pub issued_at: i64,
+    /// Unix timestamp (milliseconds) at which the token expires, `None` if
+    /// it never expires.
+    pub expires_at: Option<i64>,
 }
 
 worktable!(
@@ -35,6 +78,30 @@ worktable!(
         id: u64 primary_key autoincrement,
         public_id: UserPublicId,
         token: Uuid,
+        issued_at: i64,
+        expires_at: i64 optional,
+    },
+    indexes: {
+        public_id_idx: public_id,
+        token_idx: token unique,
+    },
+    queries: {
+        delete: {
+            ByPublicId() by public_id,
+            ByToken() by token,
+        }
+    }
+);
+
+worktable!(
+    name: PersistedToken,
+    persist: true,
+    columns: {
+        id: u64 primary_key autoincrement,
+        public_id: UserPublicId,
+        token: Uuid,
+        issued_at: i64,
+        expires_at: i64 optional,
     },
     indexes: {
         public_id_idx: public_id,
@@ -43,32 +110,298 @@ worktable!(
     queries: {
         delete: {
             ByPublicId() by public_id,
+            ByToken() by token,
         }
     }
 );
 
 /// [`TokenWorkTable`] wrapper implementing [`TokenStorage`].
 #[derive(Default)]
-pub struct TokenWorkTableStorage(TokenWorkTable);
+pub struct TokenWorkTableStorage {
+    table: TokenWorkTable,
+    max_sessions_per_user: Option<usize>,
+    session_registry: Option<Arc<SessionRegistry>>,
+}
 
-#[async_trait]
-impl TokenStorage for TokenWorkTableStorage {
-    async fn store_token(&self, user_pub_id: UserPublicId, token: Uuid) -> eyre::Result<()> {
-        self.0.insert(TokenRow {
-            id: self.0.get_next_pk().into(),
-            public_id: user_pub_id,
-            token,
-        })?;
-        Ok(())
+/// [`PersistedTokenWorkTable`] wrapper implementing [`TokenStorage`].
+pub struct PersistedTokenWorkTableStorage {
+    table: PersistedTokenWorkTable,
+    max_sessions_per_user: Option<usize>,
+    session_registry: Option<Arc<SessionRegistry>>,
+}
+
+impl PersistedTokenWorkTableStorage {
+    /// Opens the storage persisted in `data_dir`, loading tokens stored
+    /// before the last restart.
+    pub async fn open(data_dir: impl Into<String>) -> eyre::Result<Self> {
+        let config = DiskConfig::new_with_table_name(
+            data_dir,
+            PersistedTokenWorkTable::name_snake_case(),
+            PersistedTokenWorkTable::version(),
+        );
+        let engine = PersistedTokenPersistenceEngine::new(config).await?;
+
+        Ok(Self {
+            table: PersistedTokenWorkTable::load(engine).await?,
+            max_sessions_per_user: None,
+            session_registry: None,
+        })
     }
 
-    async fn validate_token(&self, token: Uuid) -> eyre::Result<UserPublicId> {
-        let entry = self.0.select_by_token(token).ok_or_else(|| eyre!("token not found"))?;
-        Ok(entry.public_id)
+    /// Waits until all changes are written to disk. Should be called before
+    /// shutdown, so that no stored token is lost.
+    pub async fn flush(&self) {
+        self.table.wait_for_ops().await;
     }
+}
+
+fn is_expired(expires_at: Option<i64>, now: i64) -> bool {
+    expires_at.is_some_and(|expires_at| expires_at <= now)
+}
+
+macro_rules! impl_token_storage {
+    ($storage:ty, $row:ident, $primary_key:ident) => {
+        impl $storage {
+            /// Limits the number of tokens stored per `User`. Once the limit
+            /// is reached, storing a new token evicts the oldest one.
+            #[must_use]
+            pub fn with_max_sessions_per_user(mut self, max_sessions: usize) -> Self {
+                self.max_sessions_per_user = Some(max_sessions);
+                self
+            }
+
+            /// Kicks the connections recorded in `session_registry` once
+            /// their token is revoked, evicted or removed as expired, or
+            /// their `User`'s tokens are removed.
+            #[must_use]
+            pub fn with_session_registry(mut self, session_registry: Arc<SessionRegistry>) -> Self {
+                self.session_registry = Some(session_registry);
+                self
+            }
+
+            /// Kicks the connections authorized with `tokens`, if there is a
+            /// session registry.
+            fn kick_tokens(&self, tokens: impl IntoIterator<Item = AccessToken>) {
+                if let Some(session_registry) = &self.session_registry {
+                    session_registry.kick_tokens(tokens);
+                }
+            }
+
+            /// Removes all expired tokens, returning how many were removed.
+            pub async fn remove_expired_tokens(&self) -> eyre::Result<usize> {
+                let now = get_time_milliseconds();
+                let expired = self
+                    .table
+                    .select_all()
+                    .where_by(move |row| is_expired(row.expires_at, now))
+                    .execute()?;
+
+                for row in &expired {
+                    self.table.delete($primary_key::from(row.id)).await?;
+                }
+                self.kick_tokens(expired.iter().map(|row| AccessToken::from(row.token)));
+
+                Ok(expired.len())
+            }
+
+            /// Tokens of `User` with provided [`UserPublicId`], oldest first.
+            fn user_tokens(&self, user_pub_id: UserPublicId) -> eyre::Result<Vec<$row>> {
+                let mut rows = self.table.select_by_public_id(user_pub_id).execute()?;
+                rows.sort_by_key(|row| (row.issued_at, row.id));
+                Ok(rows)
+            }
+
+            /// Evicts the oldest tokens of `User` with provided
+            /// [`UserPublicId`] above the session limit.
+            async fn evict_sessions(&self, user_pub_id: UserPublicId) -> eyre::Result<()> {
+                let Some(max_sessions) = self.max_sessions_per_user else {
+                    return Ok(());
+                };
+
+                let rows = self.user_tokens(user_pub_id)?;
+                let evicted = rows.len().saturating_sub(max_sessions);
+                let mut evicted_tokens = Vec::with_capacity(evicted);
+                for row in rows.into_iter().take(evicted) {
+                    self.table.delete($primary_key::from(row.id)).await?;
+                    evicted_tokens.push(AccessToken::from(row.token));
+                }
+                self.kick_tokens(evicted_tokens);
+
+                Ok(())
+            }
+
+            /// Spawns a task calling [`remove_expired_tokens`] every `interval`,
+            /// which stops once the storage is dropped.
+            ///
+            /// [`remove_expired_tokens`]: Self::remove_expired_tokens
+            pub fn spawn_expiry_sweeper(self: &Arc<Self>, interval: Duration) -> JoinHandle<()> {
+                let storage = Arc::downgrade(self);
+
+                tokio::spawn(async move {
+                    let mut ticker = tokio::time::interval(interval);
+                    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                    loop {
+                        ticker.tick().await;
+                        let Some(storage) = storage.upgrade() else {
+                            return;
+                        };
+                        match storage.remove_expired_tokens().await {
+                            Ok(0) => {}
+                            Ok(removed) => tracing::debug!(removed, "Removed expired tokens"),
+                            Err(err) => tracing::warn!(error = %err, "Failed to remove expired tokens"),
+                        }
+                    }
+                })
+            }
+        }
+
+        #[async_trait]
+        impl TokenStorage for $storage {
+            async fn store_token(
+                &self,
+                user_pub_id: UserPublicId,
+                token: AccessToken,
+                expires_at: Option<i64>,
+            ) -> eyre::Result<()> {
+                self.table.insert($row {
+                    id: self.table.get_next_pk().into(),
+                    public_id: user_pub_id,
+                    token: token.expose_secret(),
+                    issued_at: get_time_milliseconds(),
+                    expires_at,
+                })?;
+                self.evict_sessions(user_pub_id).await
+            }
+
+            async fn validate_token(&self, token: AccessToken) -> eyre::Result<UserPublicId> {
+                let entry = self
+                    .table
+                    .select_by_token(token.expose_secret()).ok_or_else(|| eyre!("token not found"))?;
+                if is_expired(entry.expires_at, get_time_milliseconds()) {
+                    bail!("token expired");
+                }
+                Ok(entry.public_id)
+            }
+
+            async fn remove_tokens_for_user(&self, user_pub_id: UserPublicId) -> eyre::Result<()> {
+                self.table.delete_by_public_id(user_pub_id).await?;
+                if let Some(session_registry) = &self.session_registry {
+                    session_registry.kick_user(user_pub_id);
+                }
+                Ok(())
+            }
+
+            async fn revoke_token(&self, token: AccessToken) -> eyre::Result<()> {
+                self.table.delete_by_token(token.expose_secret()).await?;
+                self.kick_tokens([token]);
+                Ok(())
+            }
+
+            async fn list_tokens_for_user(&self, user_pub_id: UserPublicId) -> eyre::Result<Vec<TokenSession>> {
+                Ok(self
+                    .user_tokens(user_pub_id)?
+                    .into_iter()
+                    .map(|row| TokenSession {
+                        token: row.token.into(),
+                        issued_at: row.issued_at,
+                        expires_at: row.expires_at,
+                    })
+                    .collect())
+            }
+        }
+    };
+}
+
+impl_token_storage!(TokenWorkTableStorage, TokenRow, TokenPrimaryKey);
+impl_token_storage!(
+    PersistedTokenWorkTableStorage,
+    PersistedTokenRow,
+    PersistedTokenPrimaryKey
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn rejects_and_sweeps_expired_tokens() {
+        let storage = TokenWorkTableStorage::default();
+        let user_pub_id = UserPublicId::from(psc_nanoid::Nanoid::new());
+        let now = get_time_milliseconds();
+        let (expired, valid, permanent) = (
+            AccessToken::generate(),
+            AccessToken::generate(),
+            AccessToken::generate(),
+        );
+
+        storage.store_token(user_pub_id, expired, Some(now - 1)).await.unwrap();
+        storage
+            .store_token(user_pub_id, valid, Some(now + 60_000))
+            .await
+            .unwrap();
+        storage.store_token(user_pub_id, permanent, None).await.unwrap();
+
+        assert!(storage.validate_token(expired).await.is_err());
+        assert_eq!(storage.validate_token(valid).await.unwrap(), user_pub_id);
+        assert_eq!(storage.validate_token(permanent).await.unwrap(), user_pub_id);
+
+        assert_eq!(storage.remove_expired_tokens().await.unwrap(), 1);
+        assert!(storage.table.select_by_token(expired.expose_secret()).is_none());
+        assert!(storage.table.select_by_token(valid.expose_secret()).is_some());
+    }
+
+    #[tokio::test]
+    async fn revokes_single_token_and_evicts_oldest_session() {
+        let storage = TokenWorkTableStorage::default().with_max_sessions_per_user(2);
+        let user_pub_id = UserPublicId::from(psc_nanoid::Nanoid::new());
+        let tokens = [
+            AccessToken::generate(),
+            AccessToken::generate(),
+            AccessToken::generate(),
+            AccessToken::generate(),
+        ];
+
+        for token in &tokens[..3] {
+            storage.store_token(user_pub_id, *token, None).await.unwrap();
+        }
+        let listed = |sessions: Vec<TokenSession>| sessions.into_iter().map(|s| s.token).collect::<Vec<_>>();
+        assert_eq!(
+            listed(storage.list_tokens_for_user(user_pub_id).await.unwrap()),
+            tokens[1..3]
+        );
+
+        storage.revoke_token(tokens[1]).await.unwrap();
+        storage.store_token(user_pub_id, tokens[3], None).await.unwrap();
+        assert!(storage.validate_token(tokens[1]).await.is_err());
+        assert_eq!(
+            listed(storage.list_tokens_for_user(user_pub_id).await.unwrap()),
+            tokens[2..4]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn persisted_tokens_survive_restart() {
+        let data_dir = std::env::temp_dir().join(format!("honey_id-tokens-{}", Uuid::new_v4()));
+        let data_dir = data_dir.to_string_lossy().into_owned();
+        let user_pub_id = UserPublicId::from(psc_nanoid::Nanoid::new());
+        let (token, removed_token) = (AccessToken::generate(), AccessToken::generate());
+        let other_user_pub_id = UserPublicId::from(psc_nanoid::Nanoid::new());
+
+        {
+            let storage = PersistedTokenWorkTableStorage::open(data_dir.clone()).await.unwrap();
+            storage.store_token(user_pub_id, token, None).await.unwrap();
+            storage
+                .store_token(other_user_pub_id, removed_token, None)
+                .await
+                .unwrap();
+            storage.remove_tokens_for_user(other_user_pub_id).await.unwrap();
+            storage.flush().await;
+        }
+
+        let storage = PersistedTokenWorkTableStorage::open(data_dir.clone()).await.unwrap();
+        assert_eq!(storage.validate_token(token).await.unwrap(), user_pub_id);
+        assert!(storage.validate_token(removed_token).await.is_err());
 
-    async fn remove_tokens_for_user(&self, user_pub_id: UserPublicId) -> eyre::Result<()> {
-        self.0.delete_by_public_id(user_pub_id).await?;
-        Ok(())
+        std::fs::remove_dir_all(data_dir).unwrap();
     }
 }
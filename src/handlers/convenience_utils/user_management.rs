@@ -1,7 +1,24 @@
+//! Definition of [`UserStorage`] and [`UserWorkTableStorage`].
+//!
+//! This module defines the in-memory [`UserWorkTable`] for storing `User`'s
+//! received from `honey.id`, keyed by their [`UserPublicId`], and the
+//! [`UserWorkTableStorage`] implementing [`UserStorage`] on top of it.
+//!
+//! Users are not persisted, so they are re-created from `honey.id` callbacks
+//! after app restart. Until then, [`UserWorkTableStorage`] treats unknown
+//! `User`'s as enabled with the default role, so that tokens persisted across
+//! the restart keep working.
+
+use std::collections::BTreeSet;
+
 use async_trait::async_trait;
+use eyre::bail;
 use psc_nanoid::{Nanoid, alphabet::Base62Alphabet};
+use worktable::prelude::*;
+use worktable::worktable;
 
-use crate::id_entities::UserPublicId;
+use crate::enums::{AuthUserStatus, HoneyUserRole};
+use crate::id_entities::{AppPublicId, UserPublicId};
 
 #[derive(Debug, Clone)]
 pub struct CreateUserInfo {
@@ -17,13 +34,342 @@ pub struct DeleteUserInfo {
     pub app_pub_id: Option<Nanoid<16, Base62Alphabet>>,
 }
 
+/// Result of [`UserStorage::create_or_update_user`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserUpsertOutcome {
+    /// The `User` was not stored before.
+    Created,
+    /// An already stored `User` was updated.
+    Updated,
+}
+
+/// Set of [`HoneyUserRole`]s of a `User`.
+///
+/// Converts into the `u32` role values expected by
+/// [`WsConnection::set_roles`](endpoint_libs::libs::ws::WsConnection::set_roles).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UserRoleSet(BTreeSet<HoneyUserRole>);
+
+impl UserRoleSet {
+    pub fn contains(&self, role: HoneyUserRole) -> bool {
+        self.0.contains(&role)
+    }
+
+    pub fn insert(&mut self, role: HoneyUserRole) -> bool {
+        self.0.insert(role)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = HoneyUserRole> + '_ {
+        self.0.iter().copied()
+    }
+
+    /// Returns the roles as `u32` values, in ascending order.
+    pub fn to_vec(&self) -> Vec<u32> {
+        self.iter().map(|role| role as u32).collect()
+    }
+}
+
+impl FromIterator<HoneyUserRole> for UserRoleSet {
+    fn from_iter<I: IntoIterator<Item = HoneyUserRole>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl From<HoneyUserRole> for UserRoleSet {
+    fn from(role: HoneyUserRole) -> Self {
+        Self::from_iter([role])
+    }
+}
+
+impl From<UserRoleSet> for Vec<u32> {
+    fn from(roles: UserRoleSet) -> Self {
+        roles.to_vec()
+    }
+}
+
 /// Defines the basic API needed by authentication
 /// methods for proper role assignments.
 #[async_trait]
 pub trait UserStorage {
-    fn get_api_roles_by_pub_id(&self, user_pub_id: UserPublicId) -> eyre::Result<Vec<u32>>;
+    async fn get_api_roles_by_pub_id(&self, user_pub_id: UserPublicId) -> eyre::Result<UserRoleSet>;
     fn get_public_roles(&self) -> &[u32];
     fn get_honey_auth_role(&self) -> u32;
-    async fn create_or_update_user(&self, user_info_request: CreateUserInfo) -> eyre::Result<()>;
+    async fn create_or_update_user(&self, user_info_request: CreateUserInfo) -> eyre::Result<UserUpsertOutcome>;
     async fn delete_user(&self, user_info: DeleteUserInfo) -> eyre::Result<()>;
+    /// Grants [`HoneyUserRole::AppAdmin`] to `User` with provided
+    /// [`UserPublicId`].
+    ///
+    /// Called once by the callback handlers when the `User` configured as
+    /// [`HoneyIdConfig::admin_pub_id`] is first created.
+    ///
+    /// Fails by default, for storages that don't keep per-`User` roles, so
+    /// that configuring an admin with such a storage isn't silently ignored.
+    ///
+    /// [`HoneyIdConfig::admin_pub_id`]: crate::HoneyIdConfig::admin_pub_id
+    async fn assign_admin_role(&self, user_pub_id: UserPublicId) -> eyre::Result<()> {
+        bail!("UserStorage doesn't support assigning the admin role to {user_pub_id}")
+    }
+    /// Returns the [`AuthUserStatus`] of `User` with provided
+    /// [`UserPublicId`].
+    ///
+    /// Returns [`AuthUserStatus::Enabled`] by default, for storages that don't
+    /// keep statuses.
+    async fn get_user_status(&self, _user_pub_id: UserPublicId) -> eyre::Result<AuthUserStatus> {
+        Ok(AuthUserStatus::Enabled)
+    }
+    /// Stores the [`AuthUserStatus`] of `User` with provided [`UserPublicId`],
+    /// as received from `honey.id`.
+    ///
+    /// Must succeed without storing anything if the `User` isn't stored.
+    /// Does nothing by default, for storages that don't keep statuses.
+    async fn set_user_status(&self, _user_pub_id: UserPublicId, _status: AuthUserStatus) -> eyre::Result<()> {
+        Ok(())
+    }
+}
+
+worktable!(
+    name: User,
+    persist: false,
+    columns: {
+        id: u64 primary_key autoincrement,
+        public_id: UserPublicId,
+        username: String,
+        app_public_id: AppPublicId optional,
+        status: AuthUserStatus,
+        role: HoneyUserRole,
+    },
+    indexes: {
+        public_id_idx: public_id unique,
+    },
+    queries: {
+        update: {
+            ProfileByPublicId(username, app_public_id) by public_id,
+            StatusByPublicId(status) by public_id,
+            RoleByPublicId(role) by public_id,
+        },
+        delete: {
+            ByPublicId() by public_id,
+        }
+    }
+);
+
+/// Roles assigned by [`UserWorkTableStorage`].
+#[derive(Debug, Clone)]
+pub struct UserRolePolicy {
+    /// Role of `User`'s created by [`UserStorage::create_or_update_user`].
+    pub default_role: HoneyUserRole,
+    /// Roles of public connections.
+    pub public_roles: Vec<HoneyUserRole>,
+    /// Role of the `honey.id` Auth server connection.
+    pub auth_role: HoneyUserRole,
+}
+
+impl Default for UserRolePolicy {
+    fn default() -> Self {
+        Self {
+            default_role: HoneyUserRole::AppNewUser,
+            public_roles: vec![HoneyUserRole::Public],
+            auth_role: HoneyUserRole::AppApiKey,
+        }
+    }
+}
+
+/// [`UserWorkTable`] wrapper implementing [`UserStorage`].
+///
+/// Each `User` has a single [`HoneyUserRole`], which is returned as its API
+/// role. `User`'s that aren't stored, e.g. after app restart, are
+/// [`AuthUserStatus::Enabled`] with [`UserRolePolicy::default_role`].
+pub struct UserWorkTableStorage {
+    table: UserWorkTable,
+    policy: UserRolePolicy,
+    public_roles: Vec<u32>,
+}
+
+impl Default for UserWorkTableStorage {
+    fn default() -> Self {
+        Self::new(UserRolePolicy::default())
+    }
+}
+
+impl UserWorkTableStorage {
+    /// Creates new empty [`UserWorkTableStorage`] assigning roles by
+    /// provided [`UserRolePolicy`].
+    pub fn new(policy: UserRolePolicy) -> Self {
+        let public_roles = policy.public_roles.iter().map(|role| *role as u32).collect();
+
+        Self {
+            table: UserWorkTable::default(),
+            policy,
+            public_roles,
+        }
+    }
+
+    /// Returns stored `User` with provided [`UserPublicId`].
+    pub fn get_user(&self, user_pub_id: UserPublicId) -> Option<UserRow> {
+        self.table.select_by_public_id(user_pub_id)
+    }
+
+    /// Changes the role of `User` with provided [`UserPublicId`].
+    pub async fn set_user_role(&self, user_pub_id: UserPublicId, role: HoneyUserRole) -> eyre::Result<()> {
+        self.table
+            .update_role_by_public_id(RoleByPublicIdQuery { role }, user_pub_id)
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl UserStorage for UserWorkTableStorage {
+    async fn get_api_roles_by_pub_id(&self, user_pub_id: UserPublicId) -> eyre::Result<UserRoleSet> {
+        let role = self
+            .get_user(user_pub_id)
+            .map_or(self.policy.default_role, |user| user.role);
+        Ok(UserRoleSet::from(role))
+    }
+
+    fn get_public_roles(&self) -> &[u32] {
+        &self.public_roles
+    }
+
+    fn get_honey_auth_role(&self) -> u32 {
+        self.policy.auth_role as u32
+    }
+
+    async fn create_or_update_user(&self, user_info_request: CreateUserInfo) -> eyre::Result<UserUpsertOutcome> {
+        let user_pub_id = UserPublicId::from(user_info_request.user_pub_id);
+        let app_public_id = user_info_request.app_pub_id.map(AppPublicId::from);
+
+        if self.get_user(user_pub_id).is_some() {
+            self.table
+                .update_profile_by_public_id(
+                    ProfileByPublicIdQuery {
+                        username: user_info_request.username,
+                        app_public_id,
+                    },
+                    user_pub_id,
+                )
+                .await?;
+
+            Ok(UserUpsertOutcome::Updated)
+        } else {
+            self.table.insert(UserRow {
+                id: self.table.get_next_pk().into(),
+                public_id: user_pub_id,
+                username: user_info_request.username,
+                app_public_id,
+                status: AuthUserStatus::Enabled,
+                role: self.policy.default_role,
+            })?;
+
+            Ok(UserUpsertOutcome::Created)
+        }
+    }
+
+    async fn delete_user(&self, user_info: DeleteUserInfo) -> eyre::Result<()> {
+        self.table
+            .delete_by_public_id(UserPublicId::from(user_info.user_pub_id))
+            .await?;
+        Ok(())
+    }
+
+    async fn assign_admin_role(&self, user_pub_id: UserPublicId) -> eyre::Result<()> {
+        self.set_user_role(user_pub_id, HoneyUserRole::AppAdmin).await
+    }
+
+    async fn get_user_status(&self, user_pub_id: UserPublicId) -> eyre::Result<AuthUserStatus> {
+        Ok(self
+            .get_user(user_pub_id)
+            .map_or(AuthUserStatus::Enabled, |user| user.status))
+    }
+
+    async fn set_user_status(&self, user_pub_id: UserPublicId, status: AuthUserStatus) -> eyre::Result<()> {
+        if self.get_user(user_pub_id).is_none() {
+            tracing::debug!(%user_pub_id, "Ignored status of unknown user");
+            return Ok(());
+        }
+
+        self.table
+            .update_status_by_public_id(StatusByPublicIdQuery { status }, user_pub_id)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn assigns_policy_roles_and_updates_users() {
+        let storage = UserWorkTableStorage::new(UserRolePolicy {
+            default_role: HoneyUserRole::AppSupport,
+            ..UserRolePolicy::default()
+        });
+        let user_pub_id = Nanoid::new();
+        let create = |username: &str| CreateUserInfo {
+            username: username.to_string(),
+            user_pub_id,
+            app_pub_id: None,
+        };
+
+        assert_eq!(
+            storage.create_or_update_user(create("alice")).await.unwrap(),
+            UserUpsertOutcome::Created
+        );
+        assert_eq!(
+            Vec::from(storage.get_api_roles_by_pub_id(user_pub_id.into()).await.unwrap()),
+            vec![HoneyUserRole::AppSupport as u32]
+        );
+        assert_eq!(storage.get_public_roles(), &[HoneyUserRole::Public as u32]);
+
+        storage
+            .set_user_role(user_pub_id.into(), HoneyUserRole::AppAdmin)
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.create_or_update_user(create("alice2")).await.unwrap(),
+            UserUpsertOutcome::Updated
+        );
+        let user = storage.get_user(user_pub_id.into()).unwrap();
+        assert_eq!(user.username, "alice2");
+        assert_eq!(user.role, HoneyUserRole::AppAdmin);
+
+        assert_eq!(
+            storage.get_user_status(user_pub_id.into()).await.unwrap(),
+            AuthUserStatus::Enabled
+        );
+        storage
+            .set_user_status(user_pub_id.into(), AuthUserStatus::Banned)
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.get_user_status(user_pub_id.into()).await.unwrap(),
+            AuthUserStatus::Banned
+        );
+        storage
+            .set_user_status(Nanoid::new().into(), AuthUserStatus::Banned)
+            .await
+            .unwrap();
+
+        storage
+            .delete_user(DeleteUserInfo {
+                user_pub_id,
+                app_pub_id: None,
+            })
+            .await
+            .unwrap();
+        assert!(storage.get_user(user_pub_id.into()).is_none());
+        assert_eq!(
+            Vec::from(storage.get_api_roles_by_pub_id(user_pub_id.into()).await.unwrap()),
+            vec![HoneyUserRole::AppSupport as u32]
+        );
+        assert_eq!(
+            storage.get_user_status(user_pub_id.into()).await.unwrap(),
+            AuthUserStatus::Enabled
+        );
+    }
 }
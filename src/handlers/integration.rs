@@ -0,0 +1,253 @@
+//! Definition of [`HoneyIdIntegration`].
+//!
+//! Wires all Auth to App and User to App handlers of this crate on an
+//! `endpoint-libs` WebSocket server in one call.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use endpoint_libs::libs::ws::{EndpointAuthController, WebsocketServer};
+
+use crate::client::HoneyIdClient;
+use crate::endpoints::connect::{HoneyAuthorizedConnectResponse, HoneyPublicConnectResponse};
+use crate::enums::HoneyEndpointMethodCode;
+use crate::handlers::auth_to_app::{
+    MethodApiKeyConnect, MethodReceiveToken, MethodReceiveUserDeleted, MethodReceiveUserInfo,
+    MethodReceiveUserStatusChanged, MethodValidateToken,
+};
+use crate::handlers::convenience_utils::audit::AuthAuditSink;
+use crate::handlers::convenience_utils::rate_limiting::AuthRateLimiter;
+use crate::handlers::convenience_utils::session_management::SessionRegistry;
+use crate::handlers::convenience_utils::token_management::TokenStorage;
+use crate::handlers::convenience_utils::user_management::UserStorage;
+use crate::handlers::user_to_app::{MethodAuthorizedConnect, MethodPublicConnect};
+use crate::id_entities::UserPublicId;
+
+/// Builder registering every `honey.id` handler on a [`WebsocketServer`].
+///
+/// All handlers share the provided [`HoneyIdClient`], [`TokenStorage`] and
+/// [`UserStorage`]. The [`HoneyIdConfig::admin_pub_id`] of the client is
+/// bootstrapped as admin.
+///
+/// # Example
+///
+/// ```ignore
+/// let mut auth_controller = EndpointAuthController::new();
+/// HoneyIdIntegration::new(honey_id_client, token_storage, user_storage)
+///     .with_token_ttl(Duration::from_secs(24 * 60 * 60))
+///     .with_session_registry(Arc::new(SessionRegistry::default()))
+///     .register(&mut server, &mut auth_controller);
+/// // Add the App's own auth endpoints here.
+/// server.set_auth_controller(auth_controller);
+/// ```
+///
+/// [`HoneyIdConfig::admin_pub_id`]: crate::HoneyIdConfig::admin_pub_id
+pub struct HoneyIdIntegration {
+    honey_id_client: Arc<HoneyIdClient>,
+    token_storage: Arc<dyn TokenStorage + Sync + Send>,
+    user_storage: Arc<dyn UserStorage + Send + Sync>,
+    token_ttl: Option<Duration>,
+    admin_pub_id: Option<UserPublicId>,
+    session_registry: Option<Arc<SessionRegistry>>,
+    rate_limiter: Option<Arc<dyn AuthRateLimiter>>,
+    audit_sink: Option<Arc<dyn AuthAuditSink>>,
+    authorized_connect: Option<MethodAuthorizedConnect>,
+    public_connect: Option<MethodPublicConnect>,
+}
+
+impl HoneyIdIntegration {
+    pub fn new(
+        honey_id_client: Arc<HoneyIdClient>,
+        token_storage: Arc<dyn TokenStorage + Sync + Send>,
+        user_storage: Arc<dyn UserStorage + Send + Sync>,
+    ) -> Self {
+        let admin_pub_id = honey_id_client.config().admin_pub_id.map(UserPublicId::from);
+
+        Self {
+            honey_id_client,
+            token_storage,
+            user_storage,
+            token_ttl: None,
+            admin_pub_id,
+            session_registry: None,
+            rate_limiter: None,
+            audit_sink: None,
+            authorized_connect: None,
+            public_connect: None,
+        }
+    }
+
+    /// Sets how long received tokens stay valid. Tokens never expire by
+    /// default.
+    #[must_use]
+    pub fn with_token_ttl(mut self, token_ttl: Duration) -> Self {
+        self.token_ttl = Some(token_ttl);
+        self
+    }
+
+    /// Overrides the admin bootstrapped from [`HoneyIdConfig::admin_pub_id`],
+    /// `None` disables the bootstrap.
+    ///
+    /// [`HoneyIdConfig::admin_pub_id`]: crate::HoneyIdConfig::admin_pub_id
+    #[must_use]
+    pub fn with_admin_pub_id(mut self, admin_pub_id: Option<UserPublicId>) -> Self {
+        self.admin_pub_id = admin_pub_id;
+        self
+    }
+
+    /// Kicks authorized connections through `session_registry` once their
+    /// `User` is deleted, banned or disabled.
+    ///
+    /// Give the token storage the same registry (e.g. with
+    /// [`TokenWorkTableStorage::with_session_registry`]) to also kick
+    /// connections of revoked, evicted or expired tokens.
+    ///
+    /// [`TokenWorkTableStorage::with_session_registry`]: crate::handlers::convenience_utils::token_management::TokenWorkTableStorage::with_session_registry
+    #[must_use]
+    pub fn with_session_registry(mut self, session_registry: Arc<SessionRegistry>) -> Self {
+        self.session_registry = Some(session_registry);
+        self
+    }
+
+    /// Throttles failed `AuthorizedConnect` and `ApiKeyConnect` attempts with
+    /// `rate_limiter`.
+    #[must_use]
+    pub fn with_rate_limiter(mut self, rate_limiter: Arc<dyn AuthRateLimiter>) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// Reports audit events of the Auth to App handlers to `audit_sink`.
+    #[must_use]
+    pub fn with_audit_sink(mut self, audit_sink: Arc<dyn AuthAuditSink>) -> Self {
+        self.audit_sink = Some(audit_sink);
+        self
+    }
+
+    /// Replaces the default [`MethodAuthorizedConnect`], which only assigns
+    /// the `User`'s roles.
+    ///
+    /// The session registry and rate limiter of this builder are applied
+    /// unless `authorized_connect` has its own.
+    #[must_use]
+    pub fn with_authorized_connect(mut self, authorized_connect: MethodAuthorizedConnect) -> Self {
+        self.authorized_connect = Some(authorized_connect);
+        self
+    }
+
+    /// Replaces the default [`MethodPublicConnect`], which only assigns the
+    /// public roles.
+    #[must_use]
+    pub fn with_public_connect(mut self, public_connect: MethodPublicConnect) -> Self {
+        self.public_connect = Some(public_connect);
+        self
+    }
+
+    /// Registers the callback handlers on `server` and the connect endpoints
+    /// on `auth_controller`.
+    ///
+    /// `auth_controller` still has to be set on `server` via
+    /// [`WebsocketServer::set_auth_controller`].
+    ///
+    /// # Panics
+    ///
+    /// If any of the handlers is already registered on `server`.
+    pub fn register(self, server: &mut WebsocketServer, auth_controller: &mut EndpointAuthController) {
+        server.add_handler(MethodReceiveToken {
+            token_storage: self.token_storage.clone(),
+            user_storage: self.user_storage.clone(),
+            token_ttl: self.token_ttl,
+            admin_pub_id: self.admin_pub_id,
+            audit_sink: self.audit_sink.clone(),
+        });
+        server.add_handler(MethodReceiveUserInfo {
+            token_storage: self.token_storage.clone(),
+            user_storage: self.user_storage.clone(),
+            token_ttl: self.token_ttl,
+            admin_pub_id: self.admin_pub_id,
+            audit_sink: self.audit_sink.clone(),
+        });
+        server.add_handler(MethodReceiveUserDeleted {
+            token_storage: self.token_storage.clone(),
+            user_storage: self.user_storage.clone(),
+            session_registry: self.session_registry.clone(),
+            audit_sink: self.audit_sink.clone(),
+        });
+        server.add_handler(MethodReceiveUserStatusChanged {
+            token_storage: self.token_storage.clone(),
+            user_storage: self.user_storage.clone(),
+            session_registry: self.session_registry.clone(),
+            audit_sink: self.audit_sink.clone(),
+        });
+        server.add_handler(MethodValidateToken {
+            token_storage: self.token_storage.clone(),
+            audit_sink: self.audit_sink.clone(),
+        });
+
+        auth_controller.add_auth_endpoint(
+            HoneyEndpointMethodCode::ApiKeyConnect.schema(),
+            MethodApiKeyConnect {
+                honey_id_client: self.honey_id_client,
+                user_storage: self.user_storage.clone(),
+                rate_limiter: self.rate_limiter.clone(),
+                audit_sink: self.audit_sink,
+            },
+        );
+
+        let mut authorized_connect = self.authorized_connect.unwrap_or_else(|| {
+            MethodAuthorizedConnect::new(self.token_storage, self.user_storage.clone(), |_req, _ctx| async {
+                Ok(HoneyAuthorizedConnectResponse {})
+            })
+        });
+        authorized_connect.session_registry = authorized_connect.session_registry.or(self.session_registry);
+        authorized_connect.rate_limiter = authorized_connect.rate_limiter.or(self.rate_limiter);
+        auth_controller.add_auth_endpoint(HoneyEndpointMethodCode::AuthorizedConnect.schema(), authorized_connect);
+
+        let public_connect = self.public_connect.unwrap_or_else(|| {
+            MethodPublicConnect::new(self.user_storage, |_req, _ctx| async {
+                Ok(HoneyPublicConnectResponse {})
+            })
+        });
+        auth_controller.add_auth_endpoint(HoneyEndpointMethodCode::PublicConnect.schema(), public_connect);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HoneyIdConfig;
+    use crate::handlers::convenience_utils::token_management::TokenWorkTableStorage;
+    use crate::handlers::convenience_utils::user_management::UserWorkTableStorage;
+    use endpoint_libs::libs::ws::WsServerConfig;
+
+    #[test]
+    fn registers_all_handlers() {
+        let mut server = WebsocketServer::new(WsServerConfig::default());
+        let mut auth_controller = EndpointAuthController::new();
+
+        HoneyIdIntegration::new(
+            Arc::new(HoneyIdClient::new(HoneyIdConfig::default())),
+            Arc::new(TokenWorkTableStorage::default()),
+            Arc::new(UserWorkTableStorage::default()),
+        )
+        .register(&mut server, &mut auth_controller);
+
+        let mut handler_codes: Vec<_> = server.handlers.keys().copied().collect();
+        handler_codes.sort();
+        assert_eq!(
+            handler_codes,
+            [
+                HoneyEndpointMethodCode::ReceiveToken,
+                HoneyEndpointMethodCode::ReceiveUserInfo,
+                HoneyEndpointMethodCode::ReceiveUserDeleted,
+                HoneyEndpointMethodCode::ValidateToken,
+                HoneyEndpointMethodCode::ReceiveUserStatusChanged,
+            ]
+            .map(|method| method as u32)
+        );
+
+        let mut auth_endpoints: Vec<_> = auth_controller.auth_endpoints.keys().cloned().collect();
+        auth_endpoints.sort();
+        assert_eq!(auth_endpoints, ["apikeyconnect", "authorizedconnect", "publicconnect"]);
+    }
+}
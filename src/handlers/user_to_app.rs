@@ -10,7 +10,7 @@ use crate::handlers::convenience_utils::generic_auth_handler::{
 // Trait implementations for Honey types
 impl AuthorizedConnectRequest for HoneyAuthorizedConnectRequest {
     fn get_access_token(&self) -> &str {
-        &self.accessToken
+        self.accessToken.expose_secret()
     }
 }
 
@@ -1,11 +1,21 @@
 mod client;
 mod config;
 pub mod handlers;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 mod types;
 
-pub use client::HoneyIdClient;
-pub use client::honeyid_ws_conn::HoneyIdConnection;
+pub use client::auth_session::{AuthenticatedSession, PendingPasswordSession};
+pub use client::honeyid_ws_conn::{DEFAULT_REQUEST_TIMEOUT, HoneyIdConnection};
+pub use client::platform_client::HoneyIdPlatformClient;
+pub use client::reconnecting::{
+    HoneyIdConnectionState, HoneyIdHandshake, ReconnectPolicy, ReconnectingHoneyIdConnection,
+};
+pub use client::role_connection::{HoneyIdConnectionRole, HoneyIdRoleConnection, RoleRequest, roles};
+pub use client::{ApiKeyError, ApiKeySlot, HoneyIdClient, SignInError, SignInOutcome, SignupOutcome};
 pub use config::HoneyIdConfig;
+pub use handlers::integration::HoneyIdIntegration;
+pub use types::credentials;
 pub use types::endpoints;
 pub use types::enums;
 pub use types::error;
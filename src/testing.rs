@@ -0,0 +1,10 @@
+//! Test utilities, only available with the `testing` feature.
+//!
+//! Allows covering code that talks to `honey.id` without reaching the real
+//! Auth server, and connect handlers without a WebSocket server.
+
+pub mod fakes;
+pub mod mock_server;
+
+pub use fakes::{AuthFailure, FakeConnection};
+pub use mock_server::{MockApp, MockCallbackClient, MockHoneyIdServer, MockUser};
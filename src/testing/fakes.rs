@@ -0,0 +1,127 @@
+//! Definition of [`FakeConnection`].
+//!
+//! Lets [`SubAuthController`]s, such as the connect handlers of this crate,
+//! be unit-tested without a WebSocket server.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+use endpoint_libs::libs::error_code::ErrorCode;
+use endpoint_libs::libs::handler::HandlerError;
+use endpoint_libs::libs::peer::{Extensions, PeerIdentity};
+use endpoint_libs::libs::toolbox::{ArcToolbox, CustomError, RequestContext, Toolbox};
+use endpoint_libs::libs::ws::{SubAuthController, WsConnection, WsRequest};
+
+/// ID of the next [`FakeConnection`], so that fakes don't collide in a
+/// [`SessionRegistry`](crate::handlers::convenience_utils::session_management::SessionRegistry).
+static NEXT_CONNECTION_ID: AtomicU32 = AtomicU32::new(1);
+
+/// [`WsConnection`] and [`ArcToolbox`] that aren't backed by a server.
+///
+/// Messages sent through the toolbox are dropped.
+///
+/// # Example
+///
+/// ```ignore
+/// let fake = FakeConnection::default();
+/// let result = fake.auth(Arc::new(handler), HoneyPublicConnectRequest {}).await;
+///
+/// assert!(result.is_ok());
+/// assert_eq!(fake.roles(), [HoneyUserRole::Public as u32]);
+/// ```
+#[derive(Clone)]
+pub struct FakeConnection {
+    conn: Arc<WsConnection>,
+    toolbox: ArcToolbox,
+}
+
+impl Default for FakeConnection {
+    /// Creates new [`FakeConnection`] of an [`Unknown`](PeerIdentity::Unknown)
+    /// peer.
+    fn default() -> Self {
+        Self::new(PeerIdentity::Unknown)
+    }
+}
+
+impl FakeConnection {
+    /// Creates new [`FakeConnection`] of provided `peer`, without roles.
+    pub fn new(peer: PeerIdentity) -> Self {
+        let conn = WsConnection {
+            connection_id: NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed),
+            user_id: AtomicU64::new(0),
+            roles: Default::default(),
+            peer,
+            extensions: Extensions::new(),
+            log_id: 0,
+        };
+
+        Self {
+            conn: Arc::new(conn),
+            toolbox: Toolbox::new(),
+        }
+    }
+
+    pub fn connection(&self) -> &Arc<WsConnection> {
+        &self.conn
+    }
+
+    pub fn toolbox(&self) -> &ArcToolbox {
+        &self.toolbox
+    }
+
+    /// Returns the roles currently set on the connection.
+    pub fn roles(&self) -> Vec<u32> {
+        self.conn.get_roles().to_vec()
+    }
+
+    /// Calls [`SubAuthController::auth`] of `controller` on this connection
+    /// with `req`, the way the server does on a connect handshake.
+    pub async fn auth<T>(
+        &self,
+        controller: Arc<T>,
+        req: T::Request,
+    ) -> Result<<T::Request as WsRequest>::Response, AuthFailure>
+    where
+        T: SubAuthController,
+    {
+        let ctx = RequestContext::from_conn(&self.conn);
+
+        controller
+            .auth(&self.toolbox, req, ctx, self.conn.clone())
+            .await
+            .map_err(|err| match err {
+                HandlerError::Public(err) => AuthFailure::Public(err.into()),
+                HandlerError::Internal(err) => AuthFailure::Internal(err),
+                HandlerError::NoResponse => AuthFailure::NoResponse,
+            })
+    }
+}
+
+/// Failure of [`FakeConnection::auth`].
+#[derive(Debug)]
+pub enum AuthFailure {
+    /// Error sent to the client.
+    Public(CustomError),
+    /// Internal error, sent to the client as an opaque internal error.
+    Internal(eyre::Report),
+    /// The handler chose not to respond.
+    NoResponse,
+}
+
+impl AuthFailure {
+    /// Returns the [`ErrorCode`] of a [`Public`](Self::Public) failure.
+    pub fn code(&self) -> Option<ErrorCode> {
+        match self {
+            Self::Public(err) => Some(err.code),
+            Self::Internal(_) | Self::NoResponse => None,
+        }
+    }
+
+    /// Returns the message of a [`Public`](Self::Public) failure, if any.
+    pub fn message(&self) -> Option<&str> {
+        match self {
+            Self::Public(err) => err.params.get("message")?.as_str(),
+            Self::Internal(_) | Self::NoResponse => None,
+        }
+    }
+}
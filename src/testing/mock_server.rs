@@ -0,0 +1,956 @@
+//! Definition of [`MockHoneyIdServer`] and [`MockCallbackClient`].
+//!
+//! In-process stand-in for `honey.id`, serving the auth flow and platform
+//! endpoints from in-memory state and calling the callback endpoints of the
+//! `App` under test the way the real Auth server does.
+
+use std::collections::HashMap;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use endpoint_libs::libs::handler::{HandlerError, RequestHandler, Response};
+use endpoint_libs::libs::toolbox::{ArcToolbox, CustomError, RequestContext};
+use endpoint_libs::libs::utils::get_time_milliseconds;
+use endpoint_libs::libs::ws::{
+    EndpointAuthController, SubAuthController, WebsocketServer, WsConnection, WsServerConfig,
+};
+use futures::FutureExt;
+use futures::future::LocalBoxFuture;
+use secrecy::{ExposeSecret, SecretString};
+use tokio::sync::oneshot;
+use url::Url;
+use uuid::Uuid;
+
+use crate::HoneyIdConfig;
+use crate::client::HoneyIdClient;
+use crate::client::honeyid_ws_conn::HoneyIdConnection;
+use crate::credentials::{AccessToken, AppApiKey};
+use crate::endpoints::auth_flow::{
+    HoneySignupError, HoneySignupRequest, HoneySignupResponse, HoneySubmitPasswordError, HoneySubmitPasswordRequest,
+    HoneySubmitPasswordResponse, HoneySubmitUsernameError, HoneySubmitUsernameRequest, HoneySubmitUsernameResponse,
+};
+use crate::endpoints::callback::{
+    HoneyReceiveTokenRequest, HoneyReceiveUserDeletedRequest, HoneyReceiveUserInfoRequest,
+    HoneyReceiveUserStatusChangedRequest,
+};
+use crate::endpoints::connect::{
+    HoneyApiKeyConnectError, HoneyApiKeyConnectRequest, HoneyPublicConnectRequest, HoneyPublicConnectResponse,
+};
+use crate::endpoints::platform::{
+    BanUserError, BanUserRequest, BanUserResponse, CreateAppConfigError, CreateAppConfigRequest,
+    CreateAppConfigResponse, DeleteAppConfigError, DeleteAppConfigRequest, DeleteAppConfigResponse, DeleteUserError,
+    DeleteUserRequest, DeleteUserResponse, EditAppConfigError, EditAppConfigRequest, EditAppConfigResponse,
+    GetAppSecurityRulesError, GetAppSecurityRulesRequest, GetAppSecurityRulesResponse, PlatformConnectError,
+    PlatformConnectRequest, PlatformConnectResponse, SetLogLevelError, SetLogLevelRequest, SetLogLevelResponse,
+    UnbanUserError, UnbanUserRequest, UnbanUserResponse,
+};
+use crate::enums::{AuthUserStatus, HoneyEndpointMethodCode, HoneyUserRole, LogLevel};
+use crate::types::error::{HoneyEndpointRequest, HoneyIdEndpointResult, HoneyIdResult};
+use crate::types::id_entities::{AppPublicId, UserPublicId};
+
+/// How long the username step of an auth flow stays valid.
+const AUTH_FLOW_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// How long [`MockHoneyIdServer::start`] waits for the server to listen.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// `App` registered on a [`MockHoneyIdServer`].
+#[derive(Debug, Clone)]
+pub struct MockApp {
+    pub app_public_id: AppPublicId,
+    /// `Auth` API key presented to the `App` on callback connections.
+    pub app_api_key: AppApiKey,
+    /// Address of the `App`'s callback endpoints, `None` if the `App` doesn't
+    /// receive callbacks.
+    pub callback_url: Option<Url>,
+    pub min_password_length: i32,
+    /// Characters of which a password must contain at least one, any
+    /// password is accepted if empty.
+    pub required_password_chars: String,
+}
+
+impl MockApp {
+    /// Returns a [`HoneyIdConfig`] of this `App` pointing at `server`.
+    pub fn config(&self, server: &MockHoneyIdServer) -> HoneyIdConfig {
+        HoneyIdConfig {
+            addr: server.addr().clone(),
+            app_public_id: self.app_public_id.into(),
+            auth_api_key: self.app_api_key.clone(),
+            ..HoneyIdConfig::default()
+        }
+    }
+
+    fn accepts_password(&self, password: &str) -> bool {
+        let long_enough = usize::try_from(self.min_password_length).is_ok_and(|min| password.chars().count() >= min);
+        let has_required_char = self.required_password_chars.is_empty()
+            || password.chars().any(|char| self.required_password_chars.contains(char));
+
+        long_enough && has_required_char
+    }
+}
+
+/// `User` registered on a [`MockHoneyIdServer`].
+#[derive(Debug, Clone)]
+pub struct MockUser {
+    pub user_public_id: UserPublicId,
+    pub app_public_id: AppPublicId,
+    pub username: String,
+    pub password: String,
+    pub status: AuthUserStatus,
+    pub encryption_key: String,
+}
+
+#[derive(Debug)]
+struct MockState {
+    platform_api_key: SecretString,
+    apps: HashMap<AppPublicId, MockApp>,
+    users: HashMap<UserPublicId, MockUser>,
+    /// Pending auth flows, keyed by the ID of the connection that submitted
+    /// the username.
+    auth_flows: HashMap<u32, AuthFlow>,
+    log_level: LogLevel,
+}
+
+#[derive(Debug)]
+struct AuthFlow {
+    user_public_id: UserPublicId,
+    expires_at: i64,
+}
+
+impl MockState {
+    fn user_by_username(&self, app_public_id: AppPublicId, username: &str) -> Option<&MockUser> {
+        self.users
+            .values()
+            .find(|user| user.app_public_id == app_public_id && user.username == username)
+    }
+}
+
+type SharedState = Arc<Mutex<MockState>>;
+
+fn lock(state: &SharedState) -> MutexGuard<'_, MockState> {
+    state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// In-process mock of the `honey.id` Auth server, for integration tests.
+///
+/// Serves [`PublicConnect`], [`Signup`], [`SubmitUsername`],
+/// [`SubmitPassword`], [`PlatformConnect`] and the platform endpoints over
+/// plain `ws://` on a local port. `Users` and `Apps` only live in memory.
+///
+/// Like the real Auth server, [`Signup`], [`SubmitPassword`] and the
+/// platform endpoints changing a `User` call the callback endpoints of the
+/// `User`'s `App`, if it has a [`callback_url`](MockApp::callback_url).
+///
+/// The server stops once dropped.
+///
+/// # Example
+///
+/// ```ignore
+/// let server = MockHoneyIdServer::start(SecretString::from("platform-key")).await?;
+/// let app = server.create_app(None);
+/// let client = HoneyIdClient::new(app.config(&server));
+///
+/// client.signup("alice", "correct horse").await?;
+/// client.sign_in("alice", "correct horse").await?;
+/// ```
+///
+/// [`PublicConnect`]: crate::endpoints::connect::HoneyPublicConnectRequest
+/// [`Signup`]: crate::endpoints::auth_flow::HoneySignupRequest
+/// [`SubmitUsername`]: crate::endpoints::auth_flow::HoneySubmitUsernameRequest
+/// [`SubmitPassword`]: crate::endpoints::auth_flow::HoneySubmitPasswordRequest
+/// [`PlatformConnect`]: crate::endpoints::platform::PlatformConnectRequest
+#[derive(Debug)]
+pub struct MockHoneyIdServer {
+    addr: Url,
+    state: SharedState,
+    /// Stops the server thread once dropped.
+    _shutdown: oneshot::Sender<()>,
+}
+
+impl MockHoneyIdServer {
+    /// Starts a new [`MockHoneyIdServer`] on a free local port, accepting
+    /// `platform_api_key` on [`PlatformConnect`].
+    ///
+    /// [`PlatformConnect`]: crate::endpoints::platform::PlatformConnectRequest
+    pub async fn start(platform_api_key: SecretString) -> eyre::Result<Self> {
+        let state = Arc::new(Mutex::new(MockState {
+            platform_api_key,
+            apps: HashMap::new(),
+            users: HashMap::new(),
+            auth_flows: HashMap::new(),
+            log_level: LogLevel::Info,
+        }));
+
+        let local_addr = free_local_addr()?;
+        let mut server = WebsocketServer::new(WsServerConfig {
+            name: "mock-honey-id".to_string(),
+            address: local_addr.to_string(),
+            insecure: true,
+            ..WsServerConfig::default()
+        });
+
+        server.add_handler(MethodSignup { state: state.clone() });
+        server.add_handler(MethodSubmitUsername { state: state.clone() });
+        server.add_handler(MethodSubmitPassword { state: state.clone() });
+        server.add_handler(MethodCreateAppConfig { state: state.clone() });
+        server.add_handler(MethodBanUser { state: state.clone() });
+        server.add_handler(MethodUnbanUser { state: state.clone() });
+        server.add_handler(MethodDeleteUser { state: state.clone() });
+        server.add_handler(MethodDeleteAppConfig { state: state.clone() });
+        server.add_handler(MethodEditAppConfig { state: state.clone() });
+        server.add_handler(MethodGetAppSecurityRules { state: state.clone() });
+        server.add_handler(MethodSetLogLevel { state: state.clone() });
+
+        let mut auth_controller = EndpointAuthController::new();
+        auth_controller.add_auth_endpoint(HoneyEndpointMethodCode::PublicConnect.schema(), MockPublicConnect);
+        auth_controller.add_auth_endpoint(
+            HoneyEndpointMethodCode::PlatformConnect.schema(),
+            MockPlatformConnect { state: state.clone() },
+        );
+        server.set_auth_controller(auth_controller);
+
+        let shutdown = spawn_server(server, local_addr).await?;
+
+        Ok(Self {
+            addr: Url::parse(&format!("ws://{local_addr}"))?,
+            state,
+            _shutdown: shutdown,
+        })
+    }
+
+    /// Returns the address to [`connect`](HoneyIdConfig::addr) to.
+    pub fn addr(&self) -> &Url {
+        &self.addr
+    }
+
+    /// Registers a new `App` with default security rules, receiving
+    /// callbacks at `callback_url` if provided.
+    pub fn create_app(&self, callback_url: Option<Url>) -> MockApp {
+        let app = new_app(AppPublicId::default(), callback_url);
+        lock(&self.state).apps.insert(app.app_public_id, app.clone());
+
+        app
+    }
+
+    /// Returns the `App` with provided ID, if registered.
+    pub fn app(&self, app_public_id: AppPublicId) -> Option<MockApp> {
+        lock(&self.state).apps.get(&app_public_id).cloned()
+    }
+
+    /// Returns the `User` of `app_public_id` with provided `username`, if
+    /// registered.
+    pub fn user(&self, app_public_id: AppPublicId, username: &str) -> Option<MockUser> {
+        lock(&self.state).user_by_username(app_public_id, username).cloned()
+    }
+
+    /// Returns the log level last set via [`SetLogLevel`].
+    ///
+    /// [`SetLogLevel`]: crate::endpoints::platform::SetLogLevelRequest
+    pub fn log_level(&self) -> LogLevel {
+        lock(&self.state).log_level
+    }
+
+    /// Opens a [`MockCallbackClient`] to the callback endpoints of `app`.
+    pub async fn callback_client(
+        &self,
+        app: &MockApp,
+    ) -> HoneyIdEndpointResult<MockCallbackClient, HoneyApiKeyConnectError> {
+        let callback_url = app
+            .callback_url
+            .as_ref()
+            .ok_or_else(|| eyre::eyre!("App {} has no callback url", app.app_public_id))?;
+
+        MockCallbackClient::connect(callback_url, &app.app_api_key).await
+    }
+}
+
+/// Connection to the callback endpoints of an `App`, authorized the way
+/// `honey.id` does via [`ApiKeyConnect`].
+///
+/// Lets tests drive [`ReceiveToken`], [`ReceiveUserInfo`],
+/// [`ReceiveUserDeleted`], [`ReceiveUserStatusChanged`] and
+/// [`ValidateToken`] directly.
+///
+/// [`ApiKeyConnect`]: crate::endpoints::connect::HoneyApiKeyConnectRequest
+/// [`ReceiveToken`]: crate::endpoints::callback::HoneyReceiveTokenRequest
+/// [`ReceiveUserInfo`]: crate::endpoints::callback::HoneyReceiveUserInfoRequest
+/// [`ReceiveUserDeleted`]: crate::endpoints::callback::HoneyReceiveUserDeletedRequest
+/// [`ReceiveUserStatusChanged`]: crate::endpoints::callback::HoneyReceiveUserStatusChangedRequest
+/// [`ValidateToken`]: crate::endpoints::callback::HoneyValidateTokenRequest
+#[derive(Debug)]
+pub struct MockCallbackClient {
+    conn: HoneyIdConnection,
+}
+
+impl MockCallbackClient {
+    /// Connects to the callback endpoints at `callback_url`, presenting
+    /// `app_api_key`.
+    pub async fn connect(
+        callback_url: &Url,
+        app_api_key: &AppApiKey,
+    ) -> HoneyIdEndpointResult<Self, HoneyApiKeyConnectError> {
+        let header =
+            HoneyIdClient::connect_header(HoneyEndpointMethodCode::ApiKeyConnect, &[app_api_key.expose_secret()])?;
+        let conn = HoneyIdClient::raw_connect(callback_url, &header).await?;
+
+        conn.receive_handshake::<HoneyApiKeyConnectRequest>().await?;
+
+        Ok(Self { conn })
+    }
+
+    /// Calls the callback endpoint of `req`.
+    pub async fn call<Req>(&self, req: Req) -> HoneyIdEndpointResult<Req::Response, Req::Error>
+    where
+        Req: HoneyEndpointRequest,
+    {
+        self.conn.request_typed(req).await
+    }
+
+    /// Returns the underlying [`HoneyIdConnection`].
+    pub fn connection(&self) -> &HoneyIdConnection {
+        &self.conn
+    }
+}
+
+/// Returns a local address that was free when checked.
+fn free_local_addr() -> HoneyIdResult<SocketAddr> {
+    let listener = TcpListener::bind("127.0.0.1:0").map_err(eyre::Report::from)?;
+
+    Ok(listener.local_addr().map_err(eyre::Report::from)?)
+}
+
+/// Runs `server` on a dedicated thread until the returned sender is dropped,
+/// waiting for it to listen on `local_addr`.
+async fn spawn_server(server: WebsocketServer, local_addr: SocketAddr) -> eyre::Result<oneshot::Sender<()>> {
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+
+    std::thread::Builder::new()
+        .name(format!("mock-honey-id-{local_addr}"))
+        .spawn(move || {
+            let runtime = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+                Ok(runtime) => runtime,
+                Err(err) => {
+                    tracing::error!(%err, "Failed to build mock honey.id runtime");
+                    return;
+                }
+            };
+
+            runtime.block_on(async move {
+                tokio::select! {
+                    result = server.listen() => {
+                        if let Err(err) = result {
+                            tracing::error!(%err, "Mock honey.id server stopped");
+                        }
+                    }
+                    _ = shutdown_rx => {}
+                }
+            });
+        })?;
+
+    let started_at = tokio::time::Instant::now();
+    while TcpStream::connect(local_addr).is_err() {
+        if started_at.elapsed() >= STARTUP_TIMEOUT {
+            eyre::bail!("Mock honey.id server didn't listen on {local_addr} within {STARTUP_TIMEOUT:?}");
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    Ok(shutdown_tx)
+}
+
+fn new_app(app_public_id: AppPublicId, callback_url: Option<Url>) -> MockApp {
+    MockApp {
+        app_public_id,
+        app_api_key: AppApiKey::from(Uuid::new_v4().simple().to_string()),
+        callback_url,
+        min_password_length: 8,
+        required_password_chars: String::new(),
+    }
+}
+
+/// Parses a `callBackUrl` of the platform endpoints, empty meaning none.
+fn parse_callback_url(callback_url: &str) -> Result<Option<Url>, url::ParseError> {
+    if callback_url.is_empty() {
+        return Ok(None);
+    }
+
+    Url::parse(callback_url).map(Some)
+}
+
+/// Calls the callback endpoint of `req` on `app`, doing nothing if the `App`
+/// doesn't receive callbacks.
+async fn callback<Req>(app: &MockApp, req: Req) -> HoneyIdEndpointResult<(), Req::Error>
+where
+    Req: HoneyEndpointRequest,
+{
+    let Some(callback_url) = &app.callback_url else {
+        return Ok(());
+    };
+
+    let client = MockCallbackClient::connect(callback_url, &app.app_api_key)
+        .await
+        .map_err(|err| eyre::eyre!("Failed to connect to callback endpoints: {err}"))?;
+    client.call(req).await?;
+
+    Ok(())
+}
+
+struct MockPublicConnect;
+
+#[async_trait(?Send)]
+impl SubAuthController for MockPublicConnect {
+    type Request = HoneyPublicConnectRequest;
+    type Error = CustomError;
+
+    fn auth(
+        self: Arc<Self>,
+        _toolbox: &ArcToolbox,
+        _req: Self::Request,
+        _ctx: RequestContext,
+        conn: Arc<WsConnection>,
+    ) -> LocalBoxFuture<'static, Response<Self::Request, Self::Error>> {
+        async move {
+            conn.set_roles(Arc::new(vec![HoneyUserRole::Public as u32]));
+
+            Ok(HoneyPublicConnectResponse {})
+        }
+        .boxed_local()
+    }
+}
+
+struct MockPlatformConnect {
+    state: SharedState,
+}
+
+#[async_trait(?Send)]
+impl SubAuthController for MockPlatformConnect {
+    type Request = PlatformConnectRequest;
+    type Error = PlatformConnectError;
+
+    fn auth(
+        self: Arc<Self>,
+        _toolbox: &ArcToolbox,
+        req: Self::Request,
+        _ctx: RequestContext,
+        conn: Arc<WsConnection>,
+    ) -> LocalBoxFuture<'static, Response<Self::Request, Self::Error>> {
+        async move {
+            if lock(&self.state).platform_api_key.expose_secret() != req.platformApiKey.expose_secret() {
+                return Err(HandlerError::Public(PlatformConnectError::InvalidApiKey));
+            }
+            conn.set_roles(Arc::new(vec![HoneyUserRole::Platform as u32]));
+
+            Ok(PlatformConnectResponse {})
+        }
+        .boxed_local()
+    }
+}
+
+struct MethodSignup {
+    state: SharedState,
+}
+
+#[async_trait(?Send)]
+impl RequestHandler for MethodSignup {
+    type Request = HoneySignupRequest;
+    type Error = HoneySignupError;
+
+    async fn handle(&self, _ctx: RequestContext, req: Self::Request) -> Response<Self::Request, Self::Error> {
+        let app_public_id = AppPublicId::from(req.appPublicId);
+        let (app, user) = {
+            let mut state = lock(&self.state);
+            let app = state
+                .apps
+                .get(&app_public_id)
+                .cloned()
+                .ok_or(HoneySignupError::AppNotFound)?;
+            if req.username.is_empty() || state.user_by_username(app_public_id, &req.username).is_some() {
+                return Err(HandlerError::Public(HoneySignupError::InvalidUsername));
+            }
+            if !app.accepts_password(req.password.expose_secret()) {
+                return Err(HandlerError::Public(HoneySignupError::InvalidPassword));
+            }
+
+            let user = MockUser {
+                user_public_id: UserPublicId::default(),
+                app_public_id,
+                username: req.username,
+                password: req.password.expose_secret().to_string(),
+                status: AuthUserStatus::Enabled,
+                encryption_key: Uuid::new_v4().simple().to_string(),
+            };
+            state.users.insert(user.user_public_id, user.clone());
+
+            (app, user)
+        };
+
+        let access_token = AccessToken::generate();
+        callback(
+            &app,
+            HoneyReceiveUserInfoRequest {
+                userPubId: user.user_public_id.into(),
+                username: user.username,
+                appPubId: Some(app_public_id.into()),
+                token: Some(access_token),
+            },
+        )
+        .await
+        .map_err(|err| {
+            tracing::warn!(%err, "ReceiveUserInfo callback failed");
+            HoneySignupError::CallbackFailed
+        })?;
+
+        Ok(HoneySignupResponse {
+            accessToken: access_token,
+            encryptionKey: user.encryption_key.into(),
+        })
+    }
+}
+
+struct MethodSubmitUsername {
+    state: SharedState,
+}
+
+#[async_trait(?Send)]
+impl RequestHandler for MethodSubmitUsername {
+    type Request = HoneySubmitUsernameRequest;
+    type Error = HoneySubmitUsernameError;
+
+    async fn handle(&self, ctx: RequestContext, req: Self::Request) -> Response<Self::Request, Self::Error> {
+        let app_public_id = AppPublicId::from(req.appPublicId);
+        let mut state = lock(&self.state);
+        if !state.apps.contains_key(&app_public_id) {
+            return Err(HandlerError::Public(HoneySubmitUsernameError::AppNotFound));
+        }
+        let user = state
+            .user_by_username(app_public_id, &req.username)
+            .ok_or(HoneySubmitUsernameError::UserNotFound)?;
+        if user.status != AuthUserStatus::Enabled {
+            return Err(HandlerError::Public(HoneySubmitUsernameError::AccountForbidden));
+        }
+
+        let flow = AuthFlow {
+            user_public_id: user.user_public_id,
+            expires_at: get_time_milliseconds() + AUTH_FLOW_TTL.as_millis() as i64,
+        };
+        let expires_at = flow.expires_at;
+        state.auth_flows.insert(ctx.connection_id, flow);
+
+        Ok(HoneySubmitUsernameResponse { expiresAt: expires_at })
+    }
+}
+
+struct MethodSubmitPassword {
+    state: SharedState,
+}
+
+#[async_trait(?Send)]
+impl RequestHandler for MethodSubmitPassword {
+    type Request = HoneySubmitPasswordRequest;
+    type Error = HoneySubmitPasswordError;
+
+    async fn handle(&self, ctx: RequestContext, req: Self::Request) -> Response<Self::Request, Self::Error> {
+        let (app, user) = {
+            let mut state = lock(&self.state);
+            let flow = state
+                .auth_flows
+                .remove(&ctx.connection_id)
+                .filter(|flow| flow.expires_at > get_time_milliseconds())
+                .ok_or(HoneySubmitPasswordError::AuthFlowRequired)?;
+            let user = state
+                .users
+                .get(&flow.user_public_id)
+                .cloned()
+                .ok_or(HoneySubmitPasswordError::AuthFlowRequired)?;
+            if user.password != req.password.expose_secret() {
+                // Allows retrying the password within the same flow.
+                state.auth_flows.insert(ctx.connection_id, flow);
+                return Err(HandlerError::Public(HoneySubmitPasswordError::InvalidPassword));
+            }
+            if user.status != AuthUserStatus::Enabled {
+                return Err(HandlerError::Public(HoneySubmitPasswordError::AccessDenied));
+            }
+            let app = state
+                .apps
+                .get(&user.app_public_id)
+                .cloned()
+                .ok_or(HoneySubmitPasswordError::AccessDenied)?;
+
+            (app, user)
+        };
+
+        let access_token = AccessToken::generate();
+        callback(
+            &app,
+            HoneyReceiveTokenRequest {
+                token: access_token,
+                username: user.username,
+                userPubId: user.user_public_id.into(),
+            },
+        )
+        .await
+        .map_err(|err| {
+            tracing::warn!(%err, "ReceiveToken callback failed");
+            HoneySubmitPasswordError::CallbackFailed
+        })?;
+
+        Ok(HoneySubmitPasswordResponse {
+            accessToken: access_token,
+            encryptionKey: user.encryption_key.into(),
+        })
+    }
+}
+
+struct MethodCreateAppConfig {
+    state: SharedState,
+}
+
+#[async_trait(?Send)]
+impl RequestHandler for MethodCreateAppConfig {
+    type Request = CreateAppConfigRequest;
+    type Error = CreateAppConfigError;
+
+    async fn handle(&self, _ctx: RequestContext, req: Self::Request) -> Response<Self::Request, Self::Error> {
+        let callback_url = parse_callback_url(&req.callBackUrl).map_err(|_| CreateAppConfigError::InternalError)?;
+        let app = new_app(req.appPublicId.into(), callback_url);
+        lock(&self.state).apps.insert(app.app_public_id, app.clone());
+
+        Ok(CreateAppConfigResponse {
+            appPublicId: req.appPublicId,
+            createdAt: get_time_milliseconds(),
+            appApiKey: app.app_api_key.expose_secret().into(),
+            minPasswordLength: app.min_password_length,
+            requiredPasswordChars: app.required_password_chars,
+        })
+    }
+}
+
+/// Outcome of a status change of the `User` `user_public_id` of
+/// `app_public_id`.
+enum StatusChange {
+    Changed(MockApp),
+    UserNotFound,
+    AppNotFound,
+    MembershipNotFound,
+}
+
+fn change_status(
+    state: &SharedState,
+    user_public_id: UserPublicId,
+    app_public_id: AppPublicId,
+    status: AuthUserStatus,
+) -> StatusChange {
+    let mut state = lock(state);
+    let Some(app) = state.apps.get(&app_public_id).cloned() else {
+        return StatusChange::AppNotFound;
+    };
+    let Some(user) = state.users.get_mut(&user_public_id) else {
+        return StatusChange::UserNotFound;
+    };
+    if user.app_public_id != app_public_id {
+        return StatusChange::MembershipNotFound;
+    }
+    user.status = status;
+
+    StatusChange::Changed(app)
+}
+
+/// Reports a status change of `user_public_id` to `app`, only logging
+/// failures as the change already happened.
+async fn report_status_change(app: &MockApp, user_public_id: UserPublicId, status: AuthUserStatus) {
+    let result = callback(
+        app,
+        HoneyReceiveUserStatusChangedRequest {
+            userPubId: user_public_id.into(),
+            appPubId: Some(app.app_public_id.into()),
+            status,
+        },
+    )
+    .await;
+    if let Err(err) = result {
+        tracing::warn!(%err, "ReceiveUserStatusChanged callback failed");
+    }
+}
+
+struct MethodBanUser {
+    state: SharedState,
+}
+
+#[async_trait(?Send)]
+impl RequestHandler for MethodBanUser {
+    type Request = BanUserRequest;
+    type Error = BanUserError;
+
+    async fn handle(&self, _ctx: RequestContext, req: Self::Request) -> Response<Self::Request, Self::Error> {
+        let user_public_id = UserPublicId::from(req.userPublicId);
+        let app = match change_status(
+            &self.state,
+            user_public_id,
+            req.appPublicId.into(),
+            AuthUserStatus::Banned,
+        ) {
+            StatusChange::Changed(app) => app,
+            StatusChange::UserNotFound => return Err(HandlerError::Public(BanUserError::UserNotFound)),
+            StatusChange::AppNotFound => return Err(HandlerError::Public(BanUserError::AppNotFound)),
+            StatusChange::MembershipNotFound => return Err(HandlerError::Public(BanUserError::MembershipNotFound)),
+        };
+        report_status_change(&app, user_public_id, AuthUserStatus::Banned).await;
+
+        Ok(BanUserResponse {})
+    }
+}
+
+struct MethodUnbanUser {
+    state: SharedState,
+}
+
+#[async_trait(?Send)]
+impl RequestHandler for MethodUnbanUser {
+    type Request = UnbanUserRequest;
+    type Error = UnbanUserError;
+
+    async fn handle(&self, _ctx: RequestContext, req: Self::Request) -> Response<Self::Request, Self::Error> {
+        let user_public_id = UserPublicId::from(req.userPublicId);
+        let app = match change_status(
+            &self.state,
+            user_public_id,
+            req.appPublicId.into(),
+            AuthUserStatus::Enabled,
+        ) {
+            StatusChange::Changed(app) => app,
+            StatusChange::UserNotFound => return Err(HandlerError::Public(UnbanUserError::UserNotFound)),
+            StatusChange::AppNotFound => return Err(HandlerError::Public(UnbanUserError::AppNotFound)),
+            StatusChange::MembershipNotFound => return Err(HandlerError::Public(UnbanUserError::MembershipNotFound)),
+        };
+        report_status_change(&app, user_public_id, AuthUserStatus::Enabled).await;
+
+        Ok(UnbanUserResponse {})
+    }
+}
+
+struct MethodDeleteUser {
+    state: SharedState,
+}
+
+#[async_trait(?Send)]
+impl RequestHandler for MethodDeleteUser {
+    type Request = DeleteUserRequest;
+    type Error = DeleteUserError;
+
+    async fn handle(&self, _ctx: RequestContext, req: Self::Request) -> Response<Self::Request, Self::Error> {
+        let user_public_id = UserPublicId::from(req.userPublicId);
+        let app = {
+            let mut state = lock(&self.state);
+            let user = state
+                .users
+                .remove(&user_public_id)
+                .ok_or(DeleteUserError::InternalError)?;
+            state.apps.get(&user.app_public_id).cloned()
+        };
+
+        if let Some(app) = app {
+            let result = callback(
+                &app,
+                HoneyReceiveUserDeletedRequest {
+                    userPubId: req.userPublicId,
+                    appPubId: Some(req.appPublicId),
+                },
+            )
+            .await;
+            if let Err(err) = result {
+                tracing::warn!(%err, "ReceiveUserDeleted callback failed");
+            }
+        }
+
+        Ok(DeleteUserResponse {})
+    }
+}
+
+struct MethodDeleteAppConfig {
+    state: SharedState,
+}
+
+#[async_trait(?Send)]
+impl RequestHandler for MethodDeleteAppConfig {
+    type Request = DeleteAppConfigRequest;
+    type Error = DeleteAppConfigError;
+
+    async fn handle(&self, _ctx: RequestContext, req: Self::Request) -> Response<Self::Request, Self::Error> {
+        let app_public_id = AppPublicId::from(req.appPublicId);
+        let mut state = lock(&self.state);
+        state
+            .apps
+            .remove(&app_public_id)
+            .ok_or(DeleteAppConfigError::InternalError)?;
+        state.users.retain(|_, user| user.app_public_id != app_public_id);
+
+        Ok(DeleteAppConfigResponse {})
+    }
+}
+
+struct MethodEditAppConfig {
+    state: SharedState,
+}
+
+#[async_trait(?Send)]
+impl RequestHandler for MethodEditAppConfig {
+    type Request = EditAppConfigRequest;
+    type Error = EditAppConfigError;
+
+    async fn handle(&self, _ctx: RequestContext, req: Self::Request) -> Response<Self::Request, Self::Error> {
+        let mut state = lock(&self.state);
+        let app = state
+            .apps
+            .get_mut(&AppPublicId::from(req.appPublicId))
+            .ok_or(EditAppConfigError::AppNotFound)?;
+        if let Some(callback_url) = req.callBackUrl {
+            app.callback_url = parse_callback_url(&callback_url).map_err(|_| EditAppConfigError::InternalError)?;
+        }
+        if let Some(min_password_length) = req.minPasswordLength {
+            app.min_password_length = min_password_length;
+        }
+        if let Some(required_password_chars) = req.requiredPasswordChars {
+            app.required_password_chars = required_password_chars;
+        }
+
+        Ok(EditAppConfigResponse {
+            appPublicId: req.appPublicId,
+            callBackUrl: app.callback_url.as_ref().map(Url::to_string).unwrap_or_default(),
+            minPasswordLength: app.min_password_length,
+            requiredPasswordChars: app.required_password_chars.clone(),
+        })
+    }
+}
+
+struct MethodGetAppSecurityRules {
+    state: SharedState,
+}
+
+#[async_trait(?Send)]
+impl RequestHandler for MethodGetAppSecurityRules {
+    type Request = GetAppSecurityRulesRequest;
+    type Error = GetAppSecurityRulesError;
+
+    async fn handle(&self, _ctx: RequestContext, req: Self::Request) -> Response<Self::Request, Self::Error> {
+        let state = lock(&self.state);
+        let app = state
+            .apps
+            .get(&AppPublicId::from(req.appPublicId))
+            .ok_or(GetAppSecurityRulesError::AppNotFound)?;
+
+        Ok(GetAppSecurityRulesResponse {
+            appPublicId: req.appPublicId,
+            minPasswordLength: app.min_password_length,
+            requiredPasswordChars: app.required_password_chars.clone(),
+        })
+    }
+}
+
+struct MethodSetLogLevel {
+    state: SharedState,
+}
+
+#[async_trait(?Send)]
+impl RequestHandler for MethodSetLogLevel {
+    type Request = SetLogLevelRequest;
+    type Error = SetLogLevelError;
+
+    async fn handle(&self, _ctx: RequestContext, req: Self::Request) -> Response<Self::Request, Self::Error> {
+        let mut state = lock(&self.state);
+        if let Some(log_level) = req.logLevel {
+            state.log_level = log_level;
+        }
+
+        Ok(SetLogLevelResponse {
+            logLevel: state.log_level,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HoneyIdIntegration;
+    use crate::SignInError;
+    use crate::client::role_connection::HoneyIdConnectionRole;
+    use crate::handlers::convenience_utils::token_management::{TokenStorage, TokenWorkTableStorage};
+    use crate::handlers::convenience_utils::user_management::{UserStorage, UserWorkTableStorage};
+
+    #[tokio::test]
+    async fn signs_in_and_reports_to_app() {
+        let server = MockHoneyIdServer::start(SecretString::from("platform-key"))
+            .await
+            .unwrap();
+
+        let app_addr = free_local_addr().unwrap();
+        let app = server.create_app(Some(Url::parse(&format!("ws://{app_addr}")).unwrap()));
+        let client = Arc::new(HoneyIdClient::new(app.config(&server)));
+
+        let token_storage = Arc::new(TokenWorkTableStorage::default());
+        let user_storage = Arc::new(UserWorkTableStorage::default());
+        let mut app_server = WebsocketServer::new(WsServerConfig {
+            address: app_addr.to_string(),
+            insecure: true,
+            ..WsServerConfig::default()
+        });
+        let mut auth_controller = EndpointAuthController::new();
+        HoneyIdIntegration::new(client.clone(), token_storage.clone(), user_storage.clone())
+            .register(&mut app_server, &mut auth_controller);
+        app_server.set_auth_controller(auth_controller);
+        let _app_shutdown = spawn_server(app_server, app_addr).await.unwrap();
+
+        let signup = client.signup("alice", "correct horse").await.unwrap();
+        let outcome = client.sign_in("alice", "correct horse").await.unwrap();
+
+        let user = server.user(app.app_public_id, "alice").unwrap();
+        assert_eq!(
+            token_storage.validate_token(signup.access_token).await.unwrap(),
+            user.user_public_id
+        );
+        assert_eq!(
+            token_storage.validate_token(outcome.access_token).await.unwrap(),
+            user.user_public_id
+        );
+
+        let app_client = HoneyIdClient::new(HoneyIdConfig {
+            addr: Url::parse(&format!("ws://{app_addr}")).unwrap(),
+            ..app.config(&server)
+        });
+        let conn = app_client.connect_authorized(outcome.access_token).await.unwrap();
+        assert_eq!(conn.role(), HoneyIdConnectionRole::Authorized);
+        assert!(app_client.connect_authorized(AccessToken::generate()).await.is_err());
+        let conn = app_client.connect_api_key().await.unwrap();
+        assert_eq!(conn.role(), HoneyIdConnectionRole::ApiKey);
+        let conn = app_client.connect_public().await.unwrap();
+        assert_eq!(conn.role(), HoneyIdConnectionRole::Public);
+
+        assert!(matches!(
+            client.sign_in("alice", "wrong horse").await,
+            Err(SignInError::SubmitPassword(HoneySubmitPasswordError::InvalidPassword))
+        ));
+
+        let platform_key = SecretString::from("platform-key");
+        let platform =
+            crate::HoneyIdPlatformClient::connect_with_timeout(server.addr(), &platform_key, Duration::from_secs(5))
+                .await
+                .unwrap();
+        platform
+            .ban_user(BanUserRequest {
+                userPublicId: user.user_public_id.into(),
+                appPublicId: app.app_public_id.into(),
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(
+            user_storage.get_user_status(user.user_public_id).await.unwrap(),
+            AuthUserStatus::Banned
+        );
+        assert!(matches!(
+            client.sign_in("alice", "correct horse").await,
+            Err(SignInError::SubmitUsername(HoneySubmitUsernameError::AccountForbidden))
+        ));
+    }
+}
@@ -3,6 +3,7 @@
 mod generated;
 
 // These modules re-export the generated code to improve the public API of this library
+pub mod credentials;
 pub mod endpoints;
 pub mod enums;
 pub mod error;
@@ -0,0 +1,222 @@
+//! Definition of [`AccessToken`], [`AppApiKey`] and [`RedactedString`].
+//!
+//! Secrets exchanged with `honey.id`. Their [`Debug`] output is redacted, so
+//! they can't leak through logs.
+
+use std::fmt;
+use std::str::FromStr;
+
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use subtle::ConstantTimeEq;
+use uuid::Uuid;
+
+/// Access token `honey.id` issues to a signed in `User`.
+///
+/// Serialized as the hyphenated [`Uuid`] string used on the wire.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AccessToken(Uuid);
+
+impl AccessToken {
+    /// Generates a new random [`AccessToken`].
+    pub fn generate() -> Self {
+        Self(Uuid::new_v4())
+    }
+
+    /// Returns the underlying [`Uuid`].
+    pub fn expose_secret(&self) -> Uuid {
+        self.0
+    }
+}
+
+impl From<Uuid> for AccessToken {
+    fn from(value: Uuid) -> Self {
+        Self(value)
+    }
+}
+
+impl FromStr for AccessToken {
+    type Err = InvalidAccessToken;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Uuid::try_parse(s).map(Self).map_err(|_| InvalidAccessToken)
+    }
+}
+
+impl fmt::Debug for AccessToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("AccessToken([REDACTED])")
+    }
+}
+
+impl Serialize for AccessToken {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for AccessToken {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let token = String::deserialize(deserializer)?;
+        token.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Error of parsing a malformed [`AccessToken`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, derive_more::Display)]
+#[display("Malformed access token")]
+pub struct InvalidAccessToken;
+
+impl std::error::Error for InvalidAccessToken {}
+
+/// `Auth` API key of an `App`, presented by `honey.id` on
+/// [`ApiKeyConnect`](crate::endpoints::connect::HoneyApiKeyConnectRequest).
+#[derive(Clone, Default, Deserialize)]
+#[serde(transparent)]
+pub struct AppApiKey(SecretString);
+
+impl AppApiKey {
+    /// Returns the key as sent on the wire.
+    pub fn expose_secret(&self) -> &str {
+        self.0.expose_secret()
+    }
+
+    /// Compares this key with `other` in constant time.
+    pub fn ct_eq(&self, other: &str) -> bool {
+        self.expose_secret().as_bytes().ct_eq(other.as_bytes()).into()
+    }
+}
+
+impl From<String> for AppApiKey {
+    fn from(value: String) -> Self {
+        Self(SecretString::from(value))
+    }
+}
+
+impl From<&str> for AppApiKey {
+    fn from(value: &str) -> Self {
+        Self(SecretString::from(value))
+    }
+}
+
+impl From<SecretString> for AppApiKey {
+    fn from(value: SecretString) -> Self {
+        Self(value)
+    }
+}
+
+impl From<AppApiKey> for SecretString {
+    fn from(value: AppApiKey) -> Self {
+        value.0
+    }
+}
+
+impl fmt::Debug for AppApiKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("AppApiKey([REDACTED])")
+    }
+}
+
+impl Serialize for AppApiKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.expose_secret())
+    }
+}
+
+/// Secret field of a generated endpoint type, such as a password or an API
+/// key.
+///
+/// Serialized as the plain string, like the `String` it replaces, but printed
+/// as `[REDACTED]`. Fields are marked secret in `config/secret_fields.txt`.
+#[derive(Clone, Default, Deserialize)]
+#[serde(transparent)]
+pub struct RedactedString(SecretString);
+
+impl RedactedString {
+    /// Returns the value as sent on the wire.
+    pub fn expose_secret(&self) -> &str {
+        self.0.expose_secret()
+    }
+}
+
+impl From<String> for RedactedString {
+    fn from(value: String) -> Self {
+        Self(SecretString::from(value))
+    }
+}
+
+impl From<&str> for RedactedString {
+    fn from(value: &str) -> Self {
+        Self(SecretString::from(value))
+    }
+}
+
+impl From<RedactedString> for SecretString {
+    fn from(value: RedactedString) -> Self {
+        value.0
+    }
+}
+
+impl From<RedactedString> for AppApiKey {
+    fn from(value: RedactedString) -> Self {
+        Self(value.0)
+    }
+}
+
+impl fmt::Debug for RedactedString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+impl Serialize for RedactedString {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.expose_secret())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_debug_and_round_trips_serde() {
+        let token = AccessToken::generate();
+        let key = AppApiKey::from("app-api-key");
+        assert_eq!(
+            format!("{token:?} {key:?}"),
+            "AccessToken([REDACTED]) AppApiKey([REDACTED])"
+        );
+
+        let json = serde_json::to_string(&(token, &key)).unwrap();
+        assert_eq!(json, format!(r#"["{}","app-api-key"]"#, token.expose_secret()));
+        let (parsed_token, parsed_key): (AccessToken, AppApiKey) = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed_token, token);
+        assert!(parsed_key.ct_eq("app-api-key"));
+
+        assert_eq!("not-a-uuid".parse::<AccessToken>(), Err(InvalidAccessToken));
+        assert!(serde_json::from_str::<AccessToken>(r#""not-a-uuid""#).is_err());
+    }
+
+    #[test]
+    fn converts_app_api_key_from_secret_string() {
+        let key = AppApiKey::from(SecretString::from("app-api-key"));
+        assert!(key.ct_eq("app-api-key"));
+        assert_eq!(SecretString::from(key).expose_secret(), "app-api-key");
+    }
+
+    #[test]
+    fn redacts_secret_fields_of_generated_types() {
+        use crate::types::generated::SubmitPasswordRequest;
+
+        let req = SubmitPasswordRequest {
+            password: "correct horse".into(),
+        };
+        assert_eq!(format!("{req:?}"), "SubmitPasswordRequest { password: [REDACTED] }");
+
+        let json = serde_json::to_string(&req).unwrap();
+        assert_eq!(json, r#"{"password":"correct horse"}"#);
+        let parsed: SubmitPasswordRequest = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.password.expose_secret(), "correct horse");
+    }
+}
@@ -24,6 +24,10 @@ pub mod callback {
         ReceiveUserInfoError as HoneyReceiveUserInfoError, ReceiveUserInfoRequest as HoneyReceiveUserInfoRequest,
         ReceiveUserInfoResponse as HoneyReceiveUserInfoResponse,
     };
+    pub use crate::types::generated::{
+        ReceiveUserStatusChangedRequest as HoneyReceiveUserStatusChangedRequest,
+        ReceiveUserStatusChangedResponse as HoneyReceiveUserStatusChangedResponse,
+    };
     pub use crate::types::generated::{
         ValidateTokenError as HoneyValidateTokenError, ValidateTokenRequest as HoneyValidateTokenRequest,
         ValidateTokenResponse as HoneyValidateTokenResponse,
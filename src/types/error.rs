@@ -1,9 +1,18 @@
 use derive_more::Display;
 use endpoint_libs::libs::error_code::ErrorCode;
-use std::fmt::Display;
+use endpoint_libs::libs::ws::WsRequest;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Debug, Display};
+use std::time::Duration;
+
+use crate::types::generated::*;
 
 pub type HoneyIdResult<T> = Result<T, HoneyIdError>;
 
+/// Result of a call to a `honey.id` endpoint that declares its own error enum.
+pub type HoneyIdEndpointResult<T, E> = Result<T, HoneyIdEndpointError<E>>;
+
 #[derive(Debug, Display, Clone)]
 #[display("{msg}")]
 pub struct HoneyIdError {
@@ -18,6 +27,19 @@ impl HoneyIdError {
             msg: msg.to_string(),
         }
     }
+
+    /// Error of a call the Auth server didn't answer within `timeout`.
+    pub fn timeout(timeout: Duration) -> Self {
+        Self::new(
+            EnumErrorCode::GatewayTimeout,
+            format!("honey.id did not respond within {timeout:?}"),
+        )
+    }
+
+    /// Returns `true` if this is a [`timeout`](Self::timeout) error.
+    pub fn is_timeout(&self) -> bool {
+        self.code == EnumErrorCode::GatewayTimeout.into()
+    }
 }
 
 impl From<eyre::Report> for HoneyIdError {
@@ -33,3 +55,85 @@ impl From<HoneyIdError> for eyre::Report {
         eyre::Report::msg(val.msg)
     }
 }
+
+/// Error of a call to a `honey.id` endpoint, where `E` is the endpoint's
+/// generated error enum (e.g. [`BanUserError`]).
+///
+/// [`BanUserError`]: crate::endpoints::platform::BanUserError
+#[derive(Debug, Display, Clone)]
+pub enum HoneyIdEndpointError<E: Debug> {
+    /// The Auth server rejected the call with one of the errors declared by
+    /// the endpoint.
+    #[display("{_0:?}")]
+    Endpoint(E),
+    /// Any other failure: transport, protocol, or an undeclared server error.
+    #[display("{_0}")]
+    Other(HoneyIdError),
+}
+
+impl<E: Debug> HoneyIdEndpointError<E> {
+    /// Returns the endpoint error, if the server answered with one.
+    pub fn endpoint_error(&self) -> Option<&E> {
+        match self {
+            Self::Endpoint(err) => Some(err),
+            Self::Other(_) => None,
+        }
+    }
+}
+
+impl<E: Debug> From<HoneyIdError> for HoneyIdEndpointError<E> {
+    fn from(value: HoneyIdError) -> Self {
+        Self::Other(value)
+    }
+}
+
+impl<E: Debug> From<eyre::Report> for HoneyIdEndpointError<E> {
+    fn from(value: eyre::Report) -> Self {
+        Self::Other(value.into())
+    }
+}
+
+/// Links a generated request type to the error enum declared by its endpoint,
+/// so that server errors can be decoded with [`request_typed`].
+///
+/// [`request_typed`]: crate::HoneyIdConnection::request_typed
+pub trait HoneyEndpointRequest: WsRequest {
+    type Error: DeserializeOwned + Debug;
+}
+
+/// Error enum of endpoints that declare no errors.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum NoEndpointError {}
+
+macro_rules! impl_honey_endpoint_request {
+    ($($req:ty => $err:ty),* $(,)?) => {
+        $(
+            impl HoneyEndpointRequest for $req {
+                type Error = $err;
+            }
+        )*
+    };
+}
+
+impl_honey_endpoint_request!(
+    PublicConnectRequest => NoEndpointError,
+    SignupRequest => SignupError,
+    SubmitUsernameRequest => SubmitUsernameError,
+    SubmitPasswordRequest => SubmitPasswordError,
+    PlatformConnectRequest => PlatformConnectError,
+    CreateAppConfigRequest => CreateAppConfigError,
+    BanUserRequest => BanUserError,
+    UnbanUserRequest => UnbanUserError,
+    DeleteUserRequest => DeleteUserError,
+    DeleteAppConfigRequest => DeleteAppConfigError,
+    EditAppConfigRequest => EditAppConfigError,
+    GetAppSecurityRulesRequest => GetAppSecurityRulesError,
+    SetLogLevelRequest => SetLogLevelError,
+    ApiKeyConnectRequest => ApiKeyConnectError,
+    AuthorizedConnectRequest => AuthorizedConnectError,
+    ReceiveTokenRequest => ReceiveTokenError,
+    ReceiveUserInfoRequest => ReceiveUserInfoError,
+    ReceiveUserDeletedRequest => NoEndpointError,
+    ValidateTokenRequest => ValidateTokenError,
+    ReceiveUserStatusChangedRequest => NoEndpointError,
+);
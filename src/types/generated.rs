@@ -1,3 +1,4 @@
+use crate::types::credentials::{AccessToken, RedactedString};
 use endpoint_libs::libs::error_code::ErrorCode;
 use endpoint_libs::libs::types::*;
 use endpoint_libs::libs::ws::toolbox::CustomError;
@@ -158,6 +159,8 @@ pub enum EnumEndpoint {
     ReceiveUserDeleted = 212,
     ///
     ValidateToken = 213,
+    ///
+    ReceiveUserStatusChanged = 214,
 }
 
 impl EnumEndpoint {
@@ -182,6 +185,7 @@ impl EnumEndpoint {
             Self::ReceiveUserInfo => ReceiveUserInfoRequest::SCHEMA,
             Self::ReceiveUserDeleted => ReceiveUserDeletedRequest::SCHEMA,
             Self::ValidateToken => ValidateTokenRequest::SCHEMA,
+            Self::ReceiveUserStatusChanged => ReceiveUserStatusChangedRequest::SCHEMA,
         };
         serde_json::from_str(schema).unwrap()
     }
@@ -280,7 +284,7 @@ impl From<EnumErrorCode> for ErrorCode {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ApiKeyConnectRequest {
-    pub appApiKey: String,
+    pub appApiKey: RedactedString,
 }
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -288,7 +292,7 @@ pub struct ApiKeyConnectResponse {}
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct AuthorizedConnectRequest {
-    pub accessToken: String,
+    pub accessToken: RedactedString,
 }
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -313,7 +317,7 @@ pub struct CreateAppConfigRequest {
 pub struct CreateAppConfigResponse {
     pub appPublicId: Nanoid<16, Base62Alphabet>,
     pub createdAt: i64,
-    pub appApiKey: String,
+    pub appApiKey: RedactedString,
     pub minPasswordLength: i32,
     pub requiredPasswordChars: String,
 }
@@ -368,7 +372,7 @@ pub struct GetAppSecurityRulesResponse {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct PlatformConnectRequest {
-    pub platformApiKey: String,
+    pub platformApiKey: RedactedString,
 }
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -382,7 +386,7 @@ pub struct PublicConnectResponse {}
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ReceiveTokenRequest {
-    pub token: String,
+    pub token: AccessToken,
     pub username: String,
     pub userPubId: Nanoid<16, Base62Alphabet>,
 }
@@ -407,13 +411,24 @@ pub struct ReceiveUserInfoRequest {
     #[serde(default)]
     pub appPubId: Option<Nanoid<16, Base62Alphabet>>,
     #[serde(default)]
-    pub token: Option<String>,
+    pub token: Option<AccessToken>,
 }
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ReceiveUserInfoResponse {}
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
+pub struct ReceiveUserStatusChangedRequest {
+    pub userPubId: Nanoid<16, Base62Alphabet>,
+    #[serde(default)]
+    pub appPubId: Option<Nanoid<16, Base62Alphabet>>,
+    pub status: UserStatus,
+}
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ReceiveUserStatusChangedResponse {}
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
 pub struct SetLogLevelRequest {
     #[serde(default)]
     pub logLevel: Option<LogLevel>,
@@ -428,24 +443,24 @@ pub struct SetLogLevelResponse {
 pub struct SignupRequest {
     pub appPublicId: Nanoid<16, Base62Alphabet>,
     pub username: String,
-    pub password: String,
+    pub password: RedactedString,
 }
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct SignupResponse {
-    pub accessToken: String,
-    pub encryptionKey: String,
+    pub accessToken: AccessToken,
+    pub encryptionKey: RedactedString,
 }
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct SubmitPasswordRequest {
-    pub password: String,
+    pub password: RedactedString,
 }
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct SubmitPasswordResponse {
-    pub accessToken: String,
-    pub encryptionKey: String,
+    pub accessToken: AccessToken,
+    pub encryptionKey: RedactedString,
 }
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -470,7 +485,7 @@ pub struct UnbanUserResponse {}
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ValidateTokenRequest {
-    pub token: String,
+    pub token: AccessToken,
 }
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -745,6 +760,8 @@ impl From<SetLogLevelError> for CustomError {
 pub enum ApiKeyConnectError {
     /// Wrong appApiKey
     InvalidApiKey,
+    /// Too many failed attempts
+    TooManyRequests,
 }
 
 impl From<ApiKeyConnectError> for CustomError {
@@ -753,6 +770,9 @@ impl From<ApiKeyConnectError> for CustomError {
             ApiKeyConnectError::InvalidApiKey => CustomError::new(EnumErrorCode::Unauthorized)
                 .with_message("Wrong appApiKey")
                 .with_kind("InvalidApiKey"),
+            ApiKeyConnectError::TooManyRequests => CustomError::new(EnumErrorCode::TooManyRequests)
+                .with_message("Too many failed attempts")
+                .with_kind("TooManyRequests"),
         }
     }
 }
@@ -2008,3 +2028,52 @@ impl WsRequest for ValidateTokenRequest {
 impl WsResponse for ValidateTokenResponse {
     type Request = ValidateTokenRequest;
 }
+
+impl WsRequest for ReceiveUserStatusChangedRequest {
+    type Response = ReceiveUserStatusChangedResponse;
+    const METHOD_ID: u32 = 214;
+    const ROLES: &[u32] = &[6];
+    const SCHEMA: &'static str = r#"{
+  "name": "ReceiveUserStatusChanged",
+  "code": 214,
+  "parameters": [
+    {
+      "name": "userPubId",
+      "ty": {
+        "NanoId": {
+          "len": 16
+        }
+      }
+    },
+    {
+      "name": "appPubId",
+      "ty": {
+        "Optional": {
+          "NanoId": {
+            "len": 16
+          }
+        }
+      }
+    },
+    {
+      "name": "status",
+      "ty": {
+        "EnumRef": {
+          "name": "UserStatus"
+        }
+      }
+    }
+  ],
+  "returns": [],
+  "stream_response": null,
+  "description": "Backend receives notification when the status of a user changes, e.g. after it is banned or unbanned. App should invalidate tokens of banned users.",
+  "json_schema": null,
+  "roles": [
+    "UserRole::AppApiKey"
+  ],
+  "errors": []
+}"#;
+}
+impl WsResponse for ReceiveUserStatusChangedResponse {
+    type Request = ReceiveUserStatusChangedRequest;
+}
@@ -9,9 +9,25 @@ use worktable::prelude::{MemStat, SizeMeasurable, align};
 
 /// Public identifier for an [`App`] in the `honey.id`.
 #[derive(
-    Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Display, From, Into, Archive, Deserialize, Serialize,
+    Clone,
+    Copy,
+    Debug,
+    Eq,
+    Hash,
+    Ord,
+    PartialEq,
+    PartialOrd,
+    Display,
+    From,
+    Into,
+    Archive,
+    Deserialize,
+    Serialize,
+    serde::Deserialize,
+    serde::Serialize,
 )]
-#[rkyv(derive(Debug))]
+#[rkyv(derive(Debug, PartialEq, Eq, PartialOrd, Ord))]
+#[serde(transparent)]
 pub struct AppPublicId(Nanoid<16, Base62Alphabet>);
 
 impl Default for AppPublicId {
@@ -58,9 +74,25 @@ impl AppPublicId {
 
 /// Public identifier for a [`User`] in the `honey.id`.
 #[derive(
-    Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Display, From, Into, Archive, Deserialize, Serialize,
+    Clone,
+    Copy,
+    Debug,
+    Eq,
+    Hash,
+    Ord,
+    PartialEq,
+    PartialOrd,
+    Display,
+    From,
+    Into,
+    Archive,
+    Deserialize,
+    Serialize,
+    serde::Deserialize,
+    serde::Serialize,
 )]
-#[rkyv(derive(Debug))]
+#[rkyv(derive(Debug, PartialEq, Eq, PartialOrd, Ord))]
+#[serde(transparent)]
 pub struct UserPublicId(Nanoid<16, Base62Alphabet>);
 
 impl Default for UserPublicId {